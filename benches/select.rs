@@ -0,0 +1,54 @@
+use criterion::{criterion_group, criterion_main, Criterion, Throughput};
+use lisel::index::Type;
+use lisel::pattern::Regex;
+use lisel::select::Select;
+use std::hint::black_box;
+use std::io::BufReader;
+
+/// Number of TARGET lines.
+const LINES: u64 = 100_000;
+
+fn target() -> String {
+    (1..=LINES).map(|i| format!("line {}\n", i)).collect()
+}
+
+/// Select every other line of TARGET.
+fn bench_select(c: &mut Criterion) {
+    let target = target();
+    let mut group = c.benchmark_group("select");
+    group.throughput(Throughput::Elements(LINES));
+
+    let index: String = (1..=LINES).step_by(2).map(|i| format!("{}\n", i)).collect();
+    group.bench_function("number", |b| {
+        b.iter(|| {
+            Select::new(
+                BufReader::new(target.as_bytes()),
+                BufReader::new(index.as_bytes()),
+                None,
+                false,
+            )
+            .count()
+        })
+    });
+
+    let index: String = (1..=LINES)
+        .map(|i| if i % 2 == 1 { "x\n" } else { "\n" })
+        .collect();
+    let index_type = Type::Re(Regex::new(".+").unwrap());
+    group.bench_function("regex", |b| {
+        b.iter(|| {
+            Select::new(
+                BufReader::new(target.as_bytes()),
+                BufReader::new(index.as_bytes()),
+                Some(black_box(index_type.clone())),
+                false,
+            )
+            .count()
+        })
+    });
+
+    group.finish();
+}
+
+criterion_group!(benches, bench_select);
+criterion_main!(benches);
@@ -0,0 +1,90 @@
+use std::io::{self, BufRead, Read};
+use std::sync::mpsc::Receiver;
+
+/// Reader over lines received from a channel.
+///
+/// Each received message is a line, a newline is appended if it does not end with one.
+/// Blocks until a message arrives, the disconnection of the channel is EOF.
+pub struct ChannelReader {
+    rx: Receiver<String>,
+    buf: Vec<u8>,
+    pos: usize,
+}
+
+impl ChannelReader {
+    pub fn new(rx: Receiver<String>) -> ChannelReader {
+        ChannelReader {
+            rx,
+            buf: Vec::new(),
+            pos: 0,
+        }
+    }
+}
+
+impl Read for ChannelReader {
+    fn read(&mut self, out: &mut [u8]) -> io::Result<usize> {
+        let n = {
+            let available = self.fill_buf()?;
+            let n = available.len().min(out.len());
+            out[..n].copy_from_slice(&available[..n]);
+            n
+        };
+        self.consume(n);
+        Ok(n)
+    }
+}
+
+impl BufRead for ChannelReader {
+    fn fill_buf(&mut self) -> io::Result<&[u8]> {
+        if self.pos >= self.buf.len() {
+            self.pos = 0;
+            match self.rx.recv() {
+                Ok(line) => {
+                    self.buf = line.into_bytes();
+                    if !self.buf.ends_with(b"\n") {
+                        self.buf.push(b'\n');
+                    }
+                }
+                // disconnected
+                Err(_) => self.buf.clear(),
+            }
+        }
+        Ok(&self.buf[self.pos..])
+    }
+
+    fn consume(&mut self, amt: usize) {
+        self.pos = (self.pos + amt).min(self.buf.len());
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::mpsc::channel;
+
+    macro_rules! test_channel_reader {
+        ($name:ident, $messages:expr, $want:expr) => {
+            #[test]
+            fn $name() {
+                let (tx, rx) = channel();
+                for m in $messages {
+                    tx.send(m.to_string()).unwrap();
+                }
+                drop(tx);
+                let got: Vec<String> = ChannelReader::new(rx).lines().map(|x| x.unwrap()).collect();
+                assert_eq!($want, got);
+            }
+        };
+    }
+
+    test_channel_reader!(
+        channel_reader_empty,
+        Vec::<&str>::new(),
+        Vec::<String>::new()
+    );
+    test_channel_reader!(
+        channel_reader_lines,
+        ["l1\n", "l2", "", "l4\n"],
+        vec!["l1", "l2", "", "l4"]
+    );
+}
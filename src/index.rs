@@ -1,42 +1,125 @@
 use crate::lineparse::Range;
-use regex::Regex;
+use crate::pattern::{Pattern, Regex};
+use std::time::{Duration, Instant};
 
 #[derive(Debug, Clone)]
+#[cfg_attr(
+    feature = "serde",
+    derive(serde::Serialize, serde::Deserialize),
+    serde(try_from = "TypeRepr", into = "TypeRepr")
+)]
 pub enum Type {
     Re(Regex),
+    /// Matches if any of the regular expressions matches.
+    ReAny(Vec<Regex>),
+    /// Matches if all of the regular expressions match.
+    ReAll(Vec<Regex>),
     Number(Range),
+    /// Matches every Nth line, i.e. N, 2N, 3N, ...
+    Every(u32),
 }
 
 impl Type {
     pub fn select(&self, linum: u32, line: &str) -> bool {
         match &self {
-            Type::Number(r) => match r {
-                Range::Single(n) => *n == linum,
-                Range::Interval(s, e) => *s <= linum && linum <= *e,
-            },
-            Type::Re(r) => r.is_match(line),
+            Type::Number(r) => r.contains(linum),
+            Type::Re(r) => r.matches(line),
+            Type::ReAny(rs) => rs.iter().any(|r| r.matches(line)),
+            Type::ReAll(rs) => rs.iter().all(|r| r.matches(line)),
+            Type::Every(n) => linum.is_multiple_of(*n),
+        }
+    }
+    /// Same as [Type::select] but None if matching regular expressions takes longer than `timeout`.
+    pub fn select_within(&self, linum: u32, line: &str, timeout: Duration) -> Option<bool> {
+        let deadline = Instant::now() + timeout;
+        let matches =
+            |r: &Regex| r.matches_within(line, deadline.saturating_duration_since(Instant::now()));
+        match &self {
+            Type::Re(r) => matches(r),
+            Type::ReAny(rs) => {
+                for r in rs {
+                    if matches(r)? {
+                        return Some(true);
+                    }
+                }
+                Some(false)
+            }
+            Type::ReAll(rs) => {
+                for r in rs {
+                    if !matches(r)? {
+                        return Some(false);
+                    }
+                }
+                Some(true)
+            }
+            Type::Number(_) | Type::Every(_) => Some(self.select(linum, line)),
         }
     }
     pub fn start(&self) -> u32 {
         match &self {
-            Type::Re(_) => u32::MIN,
+            Type::Re(_) | Type::ReAny(_) | Type::ReAll(_) | Type::Every(_) => u32::MIN,
             Type::Number(r) => match r {
-                Range::Single(n) => *n,
+                Range::Single(n) | Range::Relative(n) => *n,
                 Range::Interval(s, _) => *s,
             },
         }
     }
     pub fn end(&self) -> u32 {
         match &self {
-            Type::Re(_) => u32::MAX,
+            Type::Re(_) | Type::ReAny(_) | Type::ReAll(_) | Type::Every(_) => u32::MAX,
             Type::Number(r) => match r {
-                Range::Single(n) => *n,
+                Range::Single(n) | Range::Relative(n) => *n,
                 Range::Interval(_, e) => *e,
             },
         }
     }
 }
 
+/// Serializable form of [Type], regular expressions are their patterns.
+#[cfg(feature = "serde")]
+#[derive(serde::Serialize, serde::Deserialize)]
+enum TypeRepr {
+    Re(String),
+    ReAny(Vec<String>),
+    ReAll(Vec<String>),
+    Number(Range),
+    Every(u32),
+}
+
+#[cfg(feature = "serde")]
+impl From<Type> for TypeRepr {
+    fn from(t: Type) -> Self {
+        let pattern = |r: &Regex| r.as_str().to_string();
+        match t {
+            Type::Re(r) => TypeRepr::Re(pattern(&r)),
+            Type::ReAny(rs) => TypeRepr::ReAny(rs.iter().map(pattern).collect()),
+            Type::ReAll(rs) => TypeRepr::ReAll(rs.iter().map(pattern).collect()),
+            Type::Number(r) => TypeRepr::Number(r),
+            Type::Every(n) => TypeRepr::Every(n),
+        }
+    }
+}
+
+#[cfg(feature = "serde")]
+impl TryFrom<TypeRepr> for Type {
+    type Error = String;
+
+    fn try_from(t: TypeRepr) -> Result<Self, Self::Error> {
+        let compile = |s: String| Regex::new(&s).map_err(|x| x.to_string());
+        Ok(match t {
+            TypeRepr::Re(s) => Type::Re(compile(s)?),
+            TypeRepr::ReAny(ss) => {
+                Type::ReAny(ss.into_iter().map(compile).collect::<Result<_, _>>()?)
+            }
+            TypeRepr::ReAll(ss) => {
+                Type::ReAll(ss.into_iter().map(compile).collect::<Result<_, _>>()?)
+            }
+            TypeRepr::Number(r) => Type::Number(r),
+            TypeRepr::Every(n) => Type::Every(n),
+        })
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -50,6 +133,21 @@ mod tests {
         };
     }
 
+    test_type_select!(
+        type_select_number_right_open_max,
+        Type::Number(Range::Interval(5, u32::MAX)),
+        u32::MAX,
+        "",
+        true
+    );
+    test_type_select!(
+        type_select_number_right_open_before_start,
+        Type::Number(Range::Interval(5, u32::MAX)),
+        4,
+        "",
+        false
+    );
+
     test_type_select!(
         type_select_re_matched,
         Type::Re(Regex::new("a").unwrap()),
@@ -64,6 +162,43 @@ mod tests {
         "b",
         false
     );
+    test_type_select!(
+        type_select_re_any_matched_first,
+        Type::ReAny(vec![Regex::new("a").unwrap(), Regex::new("b").unwrap()]),
+        10,
+        "a",
+        true
+    );
+    test_type_select!(
+        type_select_re_any_matched_second,
+        Type::ReAny(vec![Regex::new("a").unwrap(), Regex::new("b").unwrap()]),
+        10,
+        "b",
+        true
+    );
+    test_type_select!(
+        type_select_re_any_not_matched,
+        Type::ReAny(vec![Regex::new("a").unwrap(), Regex::new("b").unwrap()]),
+        10,
+        "c",
+        false
+    );
+    test_type_select!(
+        type_select_re_all_matched,
+        Type::ReAll(vec![Regex::new("a").unwrap(), Regex::new("b").unwrap()]),
+        10,
+        "ab",
+        true
+    );
+    test_type_select!(
+        type_select_re_all_not_matched,
+        Type::ReAll(vec![Regex::new("a").unwrap(), Regex::new("b").unwrap()]),
+        10,
+        "a",
+        false
+    );
+    test_type_select!(type_select_every_matched, Type::Every(3), 6, "a", true);
+    test_type_select!(type_select_every_not_matched, Type::Every(3), 4, "a", false);
     test_type_select!(
         type_select_number_single_matched,
         Type::Number(Range::Single(10)),
@@ -99,6 +234,20 @@ mod tests {
         "a",
         true
     );
+    test_type_select!(
+        type_select_number_interval_all_matched_first,
+        Type::Number(Range::Interval(u32::MIN, u32::MAX)),
+        1,
+        "a",
+        true
+    );
+    test_type_select!(
+        type_select_number_interval_all_matched_last,
+        Type::Number(Range::Interval(u32::MIN, u32::MAX)),
+        u32::MAX,
+        "a",
+        true
+    );
     test_type_select!(
         type_select_number_interval_without_size_not_matched,
         Type::Number(Range::Interval(11, 9)),
@@ -106,4 +255,38 @@ mod tests {
         "a",
         false
     );
+
+    #[test]
+    fn type_select_within() {
+        let timeout = Duration::from_secs(10);
+        let any = Type::ReAny(vec![Regex::new("a").unwrap(), Regex::new("b").unwrap()]);
+        assert_eq!(Some(true), any.select_within(0, "b", timeout));
+        let all = Type::ReAll(vec![Regex::new("a").unwrap(), Regex::new("b").unwrap()]);
+        assert_eq!(Some(false), all.select_within(0, "b", timeout));
+        let number = Type::Number(Range::Single(3));
+        assert_eq!(Some(true), number.select_within(3, "", Duration::ZERO));
+    }
+
+    #[cfg(feature = "serde")]
+    #[test]
+    fn serde_round_trip() {
+        for t in [
+            Type::Re(Regex::new("^a(b|c)$").unwrap()),
+            Type::ReAny(vec![Regex::new("a").unwrap(), Regex::new("b").unwrap()]),
+            Type::ReAll(vec![Regex::new("a").unwrap()]),
+            Type::Number(Range::Interval(2, 5)),
+            Type::Number(Range::Single(3)),
+            Type::Every(4),
+        ] {
+            let s = serde_json::to_string(&t).unwrap();
+            let got: Type = serde_json::from_str(&s).unwrap();
+            assert_eq!(format!("{:?}", t), format!("{:?}", got), "{}", s);
+        }
+    }
+
+    #[cfg(feature = "serde")]
+    #[test]
+    fn serde_invalid_regex() {
+        assert!(serde_json::from_str::<Type>(r#"{"Re":"("}"#).is_err());
+    }
 }
@@ -1,4 +1,10 @@
+pub mod channel;
 pub mod index;
 pub mod lineparse;
+#[cfg(feature = "mmap")]
+pub mod mmap;
+#[cfg(feature = "parallel")]
+pub mod parallel;
+pub mod pattern;
 pub mod select;
 pub mod str;
@@ -1,59 +1,305 @@
 use nom::{
     branch::alt,
     bytes::complete::tag,
-    character::complete::one_of,
-    combinator::{fail, recognize},
-    multi::many1,
-    sequence::{preceded, separated_pair, terminated},
+    character::complete::{char, one_of},
+    combinator::{fail, not, recognize},
+    multi::{many0, many1},
+    sequence::{pair, preceded, separated_pair, terminated},
     IResult,
 };
 use std::clone::Clone;
 use std::cmp::PartialEq;
+use std::fmt;
 
 /// Expressions arranged in rows of index file.
 #[derive(Debug, PartialEq, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub enum Range {
     /// NATURAL_NUMBER
     Single(u32),
     /// NATURAL_NUMBER,NATURAL_NUMBER
     /// ,NATURAL_NUMBER
     /// NATURAL_NUMBER,
+    /// ,
+    ///
+    /// - can be used instead of ,
     Interval(u32, u32),
+    /// +NATURAL_NUMBER
+    ///
+    /// The line the number of lines after the previously selected line.
+    /// Before resolved by [Range::resolve], it is relative to line 0.
+    Relative(u32),
+}
+
+impl Range {
+    /// (start, end) of the range, open ends are [u32::MIN] and [u32::MAX].
+    fn bounds(&self) -> (u32, u32) {
+        match self {
+            Range::Single(n) | Range::Relative(n) => (*n, *n),
+            Range::Interval(s, e) => (*s, *e),
+        }
+    }
+
+    /// Resolve a relative range to the line `base` is the previously selected line,
+    /// other ranges are returned as they are.
+    pub fn resolve(self, base: u32) -> Range {
+        match self {
+            Range::Relative(n) => Range::Single(base.saturating_add(n)),
+            x => x,
+        }
+    }
+
+    /// Add `n` to the line numbers of the range, open ends stay open and relative ranges are kept.
+    /// None if a line number gets out of 1 to [u32::MAX].
+    pub fn shift(self, n: i64) -> Option<Range> {
+        let add = |x: u32| shift_line(x, n);
+        match self {
+            Range::Single(x) => add(x).map(Range::Single),
+            Range::Interval(s, e) => {
+                let s = if s == u32::MIN { Some(s) } else { add(s) };
+                let e = if e == u32::MAX { Some(e) } else { add(e) };
+                Some(Range::Interval(s?, e?))
+            }
+            x @ Range::Relative(_) => Some(x),
+        }
+    }
+
+    /// Whether the line number is in the range.
+    pub fn contains(&self, linum: u32) -> bool {
+        let (s, e) = self.bounds();
+        s <= linum && linum <= e
+    }
+
+    /// Whether the ranges share any line number.
+    /// Intervals whose start is greater than their end are empty, overlap nothing.
+    pub fn overlaps(&self, other: &Range) -> bool {
+        let (s1, e1) = self.bounds();
+        let (s2, e2) = other.bounds();
+        s1 <= e1 && s2 <= e2 && s1 <= e2 && s2 <= e1
+    }
+}
+
+/// Sort the ranges and coalesce overlapping or adjacent ones into minimal intervals.
+///
+/// Empty intervals, whose start is greater than their end, are dropped.
+pub fn merge_ranges(ranges: Vec<Range>) -> Vec<Range> {
+    let mut bounds: Vec<(u32, u32)> = ranges
+        .iter()
+        .map(Range::bounds)
+        .filter(|(s, e)| s <= e)
+        .collect();
+    bounds.sort();
+    let mut merged: Vec<(u32, u32)> = Vec::new();
+    for (s, e) in bounds {
+        match merged.last_mut() {
+            Some(last) if s <= last.1.saturating_add(1) => last.1 = last.1.max(e),
+            _ => merged.push((s, e)),
+        }
+    }
+    merged
+        .into_iter()
+        .map(|(s, e)| Range::Interval(s, e))
+        .collect()
+}
+
+/// Line numbers in both of the range sets as minimal intervals, see [merge_ranges].
+pub fn intersect_ranges(a: Vec<Range>, b: Vec<Range>) -> Vec<Range> {
+    let a: Vec<(u32, u32)> = merge_ranges(a).iter().map(Range::bounds).collect();
+    let b: Vec<(u32, u32)> = merge_ranges(b).iter().map(Range::bounds).collect();
+    let (mut i, mut j) = (0, 0);
+    let mut intersection = Vec::new();
+    while i < a.len() && j < b.len() {
+        let (s, e) = (a[i].0.max(b[j].0), a[i].1.min(b[j].1));
+        if s <= e {
+            intersection.push(Range::Interval(s, e));
+        }
+        // the interval ending first overlaps nothing after the other
+        if a[i].1 < b[j].1 {
+            i += 1;
+        } else {
+            j += 1;
+        }
+    }
+    intersection
+}
+
+/// Format as an expression of index file.
+impl fmt::Display for Range {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Range::Single(n) => write!(f, "{}", n),
+            Range::Interval(u32::MIN, u32::MAX) => write!(f, ","),
+            Range::Interval(u32::MIN, e) => write!(f, ",{}", e),
+            Range::Interval(s, u32::MAX) => write!(f, "{},", s),
+            Range::Interval(s, e) => write!(f, "{},{}", s, e),
+            Range::Relative(n) => write!(f, "+{}", n),
+        }
+    }
+}
+
+/// Add `n` to the line number, None if it gets out of 1 to [u32::MAX].
+fn shift_line(x: u32, n: i64) -> Option<u32> {
+    i64::from(x)
+        .checked_add(n)
+        .and_then(|x| u32::try_from(x).ok())
+        .filter(|&x| x >= 1)
 }
 
 /// Parse a natural number.
 fn natural(input: &str) -> IResult<&str, u32> {
-    let (input, value) = recognize(many1(one_of("0123456789")))(input)?;
-    let v: u32 = value.parse().unwrap();
+    let (rest, v) = whole(input)?;
     if v < 1 {
         fail(input)
     } else {
-        Ok((input, v))
+        Ok((rest, v))
+    }
+}
+
+/// Parse a natural number or zero.
+///
+/// Underscores can separate digits, e.g. 1_000.
+/// Digits prefixed with 0x are hexadecimal, e.g. 0x10.
+fn whole(input: &str) -> IResult<&str, u32> {
+    alt((hexadecimal, decimal))(input)
+}
+
+fn decimal(input: &str) -> IResult<&str, u32> {
+    let (rest, value) = digits_of("0123456789")(input)?;
+    match value.replace('_', "").parse() {
+        Ok(v) => Ok((rest, v)),
+        Err(_) => fail(input),
     }
 }
 
-fn single(input: &str) -> IResult<&str, Range> {
-    let (input, value) = natural(input)?;
-    Ok((input, Range::Single(value)))
+fn hexadecimal(input: &str) -> IResult<&str, u32> {
+    let (rest, value) = preceded(
+        alt((tag("0x"), tag("0X"))),
+        digits_of("0123456789abcdefABCDEF"),
+    )(input)?;
+    match u32::from_str_radix(&value.replace('_', ""), 16) {
+        Ok(v) => Ok((rest, v)),
+        Err(_) => fail(input),
+    }
 }
 
-fn interval_left_open(input: &str) -> IResult<&str, Range> {
-    let (input, value) = preceded(tag(","), natural)(input)?;
-    Ok((input, Range::Interval(u32::MIN, value)))
+/// Recognize digits separated by underscores.
+fn digits_of(chars: &'static str) -> impl FnMut(&str) -> IResult<&str, &str> {
+    move |input| {
+        let digits = || recognize(many1(one_of(chars)));
+        terminated(
+            recognize(pair(digits(), many0(preceded(char('_'), digits())))),
+            not(char('_')),
+        )(input)
+    }
 }
 
-fn interval_right_open(input: &str) -> IResult<&str, Range> {
-    let (input, value) = terminated(natural, tag(","))(input)?;
-    Ok((input, Range::Interval(value, u32::MAX)))
+type Number = fn(&str) -> IResult<&str, u32>;
+
+/// Parse the separator of an interval, , or -.
+fn separator(input: &str) -> IResult<&str, &str> {
+    alt((tag(","), tag("-")))(input)
 }
 
-fn interval(input: &str) -> IResult<&str, Range> {
-    let (input, (left_limit, right_limit)) = separated_pair(natural, tag(","), natural)(input)?;
-    Ok((input, Range::Interval(left_limit, right_limit)))
+fn single(number: Number) -> impl FnMut(&str) -> IResult<&str, Range> {
+    move |input| {
+        let (input, value) = number(input)?;
+        Ok((input, Range::Single(value)))
+    }
+}
+
+fn interval_left_open(number: Number) -> impl FnMut(&str) -> IResult<&str, Range> {
+    move |input| {
+        let (input, value) = alt((
+            preceded(tag(","), number),
+            // reject -N followed by a separator like -1,2, which reads as a negative number
+            terminated(preceded(tag("-"), number), not(separator)),
+        ))(input)?;
+        Ok((input, Range::Interval(u32::MIN, value)))
+    }
+}
+
+fn interval_right_open(number: Number) -> impl FnMut(&str) -> IResult<&str, Range> {
+    move |input| {
+        let (input, value) = terminated(number, separator)(input)?;
+        Ok((input, Range::Interval(value, u32::MAX)))
+    }
+}
+
+fn interval(number: Number) -> impl FnMut(&str) -> IResult<&str, Range> {
+    move |input| {
+        let (input, (left_limit, right_limit)) = separated_pair(number, separator, number)(input)?;
+        Ok((input, Range::Interval(left_limit, right_limit)))
+    }
+}
+
+/// Parse +N as N lines after the previously selected line.
+fn relative(input: &str) -> IResult<&str, Range> {
+    let (input, value) = preceded(char('+'), natural)(input)?;
+    Ok((input, Range::Relative(value)))
+}
+
+/// Parse a bare comma as the interval of all lines.
+fn interval_all(input: &str) -> IResult<&str, Range> {
+    let (input, _) = tag(",")(input)?;
+    Ok((input, Range::Interval(u32::MIN, u32::MAX)))
+}
+
+fn range_of(number: Number, input: &str) -> IResult<&str, Range> {
+    alt((
+        interval(number),
+        interval_left_open(number),
+        interval_right_open(number),
+        single(number),
+        interval_all,
+        relative,
+    ))(input)
 }
 
 pub fn range(input: &str) -> IResult<&str, Range> {
-    alt((interval, interval_left_open, interval_right_open, single))(input)
+    range_of(natural, input)
+}
+
+/// Parse an expression whose line numbers can be 0, to be shifted by [range_shifted].
+///
+/// 0 as the start of an interval is the same as the open start.
+pub fn range_with_zero(input: &str) -> IResult<&str, Range> {
+    range_of(whole, input)
+}
+
+/// Parse an expression by [range_with_zero] and add `base` to its line numbers by [Range::shift],
+/// returning the parsed range and the shifted one, None if a line number gets out of 1 to [u32::MAX].
+///
+/// Unlike [Range::shift], an explicit start 0 like 0,N is shifted, only the omitted start like ,N stays open.
+pub fn range_shifted(input: &str, base: i64) -> IResult<&str, (Range, Option<Range>)> {
+    let (rest, value) = range_with_zero(input)?;
+    let shifted = match &value {
+        // an explicit start 0, not the omitted start of ,N
+        Range::Interval(u32::MIN, e) if input.starts_with(|c: char| c.is_ascii_digit()) => {
+            let e = if *e == u32::MAX {
+                Some(*e)
+            } else {
+                shift_line(*e, base)
+            };
+            shift_line(u32::MIN, base)
+                .zip(e)
+                .map(|(s, e)| Range::Interval(s, e))
+        }
+        x => x.clone().shift(base),
+    };
+    Ok((rest, (value, shifted)))
+}
+
+/// Parse an expression whose line numbers start from 0,
+/// the result is shifted to line numbers starting from 1.
+pub fn range_zero_based(input: &str) -> IResult<&str, Range> {
+    let (input, value) = range_with_zero(input)?;
+    let value = match value {
+        Range::Single(n) => Range::Single(n.saturating_add(1)),
+        Range::Interval(s, e) => Range::Interval(s.saturating_add(1), e.saturating_add(1)),
+        // an offset, not a line number
+        Range::Relative(n) => Range::Relative(n),
+    };
+    Ok((input, value))
 }
 
 #[cfg(test)]
@@ -69,6 +315,195 @@ mod tests {
         };
     }
 
+    macro_rules! test_range_overlaps {
+        ($name:ident, $a:expr, $b:expr, $want:expr) => {
+            #[test]
+            fn $name() {
+                assert_eq!($want, $a.overlaps(&$b));
+                assert_eq!($want, $b.overlaps(&$a));
+            }
+        };
+    }
+
+    test_range_overlaps!(
+        overlaps_touching,
+        Range::Interval(1, 3),
+        Range::Interval(3, 5),
+        true
+    );
+    test_range_overlaps!(
+        overlaps_adjacent,
+        Range::Interval(1, 3),
+        Range::Interval(4, 5),
+        false
+    );
+    test_range_overlaps!(
+        overlaps_nested,
+        Range::Interval(1, 10),
+        Range::Single(4),
+        true
+    );
+    test_range_overlaps!(
+        overlaps_disjoint,
+        Range::Single(2),
+        Range::Interval(5, 8),
+        false
+    );
+    test_range_overlaps!(
+        overlaps_left_open,
+        Range::Interval(u32::MIN, 3),
+        Range::Interval(2, 8),
+        true
+    );
+    test_range_overlaps!(
+        overlaps_right_open,
+        Range::Interval(10, u32::MAX),
+        Range::Single(4_000_000_000),
+        true
+    );
+    test_range_overlaps!(
+        overlaps_right_open_disjoint,
+        Range::Interval(10, u32::MAX),
+        Range::Interval(u32::MIN, 9),
+        false
+    );
+    test_range_overlaps!(
+        overlaps_empty,
+        Range::Interval(5, 3),
+        Range::Interval(1, 10),
+        false
+    );
+
+    macro_rules! test_merge_ranges {
+        ($name:ident, $ranges:expr, $want:expr) => {
+            #[test]
+            fn $name() {
+                assert_eq!($want, merge_ranges($ranges));
+            }
+        };
+    }
+
+    test_merge_ranges!(merge_ranges_empty, vec![], Vec::<Range>::new());
+    test_merge_ranges!(
+        merge_ranges_overlapping_and_adjacent,
+        vec![
+            Range::Interval(1, 3),
+            Range::Interval(2, 5),
+            Range::Single(7),
+            Range::Single(8)
+        ],
+        vec![Range::Interval(1, 5), Range::Interval(7, 8)]
+    );
+    test_merge_ranges!(
+        merge_ranges_unsorted,
+        vec![Range::Single(9), Range::Interval(1, 2), Range::Single(5)],
+        vec![
+            Range::Interval(1, 2),
+            Range::Interval(5, 5),
+            Range::Interval(9, 9)
+        ]
+    );
+    test_merge_ranges!(
+        merge_ranges_nested,
+        vec![Range::Interval(1, 10), Range::Interval(3, 4)],
+        vec![Range::Interval(1, 10)]
+    );
+    test_merge_ranges!(
+        merge_ranges_open,
+        vec![
+            Range::Interval(5, u32::MAX),
+            Range::Interval(u32::MIN, 2),
+            Range::Single(3)
+        ],
+        vec![Range::Interval(u32::MIN, 3), Range::Interval(5, u32::MAX)]
+    );
+    test_merge_ranges!(
+        merge_ranges_drop_empty,
+        vec![Range::Interval(5, 3), Range::Single(1)],
+        vec![Range::Interval(1, 1)]
+    );
+
+    macro_rules! test_intersect_ranges {
+        ($name:ident, $a:expr, $b:expr, $want:expr) => {
+            #[test]
+            fn $name() {
+                assert_eq!($want, intersect_ranges($a, $b));
+                assert_eq!($want, intersect_ranges($b, $a));
+            }
+        };
+    }
+
+    test_intersect_ranges!(
+        intersect_ranges_empty,
+        vec![Range::Interval(1, 3)],
+        vec![],
+        Vec::<Range>::new()
+    );
+    test_intersect_ranges!(
+        intersect_ranges_disjoint,
+        vec![Range::Interval(1, 3)],
+        vec![Range::Interval(4, 6)],
+        Vec::<Range>::new()
+    );
+    test_intersect_ranges!(
+        intersect_ranges_overlapping,
+        vec![
+            Range::Interval(1, 5),
+            Range::Single(8),
+            Range::Interval(10, 12)
+        ],
+        vec![Range::Interval(3, 10), Range::Single(12)],
+        vec![
+            Range::Interval(3, 5),
+            Range::Interval(8, 8),
+            Range::Interval(10, 10),
+            Range::Interval(12, 12)
+        ]
+    );
+    test_intersect_ranges!(
+        intersect_ranges_open,
+        vec![Range::Interval(5, u32::MAX)],
+        vec![Range::Interval(u32::MIN, 7), Range::Interval(9, u32::MAX)],
+        vec![Range::Interval(5, 7), Range::Interval(9, u32::MAX)]
+    );
+    test_intersect_ranges!(
+        intersect_ranges_unmerged,
+        vec![
+            Range::Single(3),
+            Range::Interval(1, 2),
+            Range::Interval(5, 4)
+        ],
+        vec![Range::Interval(2, 6)],
+        vec![Range::Interval(2, 3)]
+    );
+
+    #[cfg(feature = "serde")]
+    #[test]
+    fn serde_round_trip() {
+        for r in [
+            Range::Single(3),
+            Range::Interval(2, 5),
+            Range::Interval(u32::MIN, u32::MAX),
+            Range::Relative(4),
+        ] {
+            let s = serde_json::to_string(&r).unwrap();
+            let got: Range = serde_json::from_str(&s).unwrap();
+            assert_eq!(r, got, "{}", s);
+        }
+    }
+
+    #[test]
+    fn contains() {
+        assert!(Range::Single(3).contains(3));
+        assert!(!Range::Single(3).contains(4));
+        assert!(Range::Interval(2, 4).contains(2));
+        assert!(Range::Interval(2, 4).contains(4));
+        assert!(!Range::Interval(2, 4).contains(5));
+        assert!(Range::Interval(u32::MIN, 4).contains(1));
+        assert!(Range::Interval(2, u32::MAX).contains(u32::MAX));
+        assert!(!Range::Interval(4, 2).contains(3));
+    }
+
     macro_rules! test_range_error {
         ($name:ident, $input:expr) => {
             #[test]
@@ -89,14 +524,197 @@ mod tests {
     test_range!(
         parse_interval_left_open,
         ",5",
-        Ok(("", Range::Interval(std::u32::MIN, 5)))
+        Ok(("", Range::Interval(u32::MIN, 5)))
     );
     test_range!(
         parse_interval_right_open,
         "5,",
-        Ok(("", Range::Interval(5, std::u32::MAX)))
+        Ok(("", Range::Interval(5, u32::MAX)))
+    );
+    test_range!(
+        parse_interval_all,
+        ",",
+        Ok(("", Range::Interval(u32::MIN, u32::MAX)))
     );
     test_range!(parse_interval_empty, "4,3", Ok(("", Range::Interval(4, 3))));
+    test_range!(
+        parse_interval_dash_empty,
+        "4-3",
+        Ok(("", Range::Interval(4, 3)))
+    );
+    test_range!(parse_interval_dash, "3-7", range("3,7"));
+    test_range!(parse_interval_dash_left_open, "-7", range(",7"));
+    test_range!(parse_interval_dash_right_open, "3-", range("3,"));
+    test_range!(
+        parse_interval_dash_value,
+        "3-7",
+        Ok(("", Range::Interval(3, 7)))
+    );
+    test_range!(
+        parse_single_underscore,
+        "1_000",
+        Ok(("", Range::Single(1000)))
+    );
+    test_range!(
+        parse_interval_underscore,
+        "1_0,2_000_0",
+        Ok(("", Range::Interval(10, 20000)))
+    );
+    test_range_error!(parse_single_error_underscore_leading, "_5");
+    test_range_error!(parse_single_error_underscore_trailing, "5_");
+    test_range_error!(parse_single_error_underscore_double, "1__0");
+    test_range_error!(parse_single_error_underscore_zero, "0_0");
     test_range_error!(parse_single_error_not_narural, "0");
     test_range_error!(parse_interval_error_not_natural, "-1,2");
+    test_range_error!(parse_interval_dash_error_not_natural, "-1-2");
+    test_range!(parse_single_hex, "0x10", Ok(("", Range::Single(16))));
+    test_range!(parse_single_hex_upper, "0XfF", Ok(("", Range::Single(255))));
+    test_range!(
+        parse_interval_hex_and_decimal,
+        "0x10,20",
+        Ok(("", Range::Interval(16, 20)))
+    );
+    test_range!(
+        parse_single_decimal_not_hex,
+        "10",
+        Ok(("", Range::Single(10)))
+    );
+    test_range_error!(parse_single_error_hex_zero, "0x0");
+    test_range_error!(parse_single_error_hex_no_digits, "0x");
+    test_range_error!(parse_single_error_hex_overflow, "0x100000000");
+    test_range_error!(parse_single_error_decimal_overflow, "99999999999");
+    test_range_error!(
+        parse_single_error_decimal_overflow_underscore,
+        "4_294_967_296"
+    );
+    test_range!(
+        parse_single_decimal_max,
+        "4294967295",
+        Ok(("", Range::Single(u32::MAX)))
+    );
+    test_range!(parse_relative, "+3", Ok(("", Range::Relative(3))));
+    test_range_error!(parse_relative_error_zero, "+0");
+    test_range_error!(parse_relative_error_no_number, "+");
+
+    macro_rules! test_range_zero_based {
+        ($name:ident, $input:expr, $want:expr) => {
+            #[test]
+            fn $name() {
+                let got = range_zero_based($input);
+                assert_eq!($want, got);
+            }
+        };
+    }
+
+    test_range_zero_based!(parse_zero_based_zero, "0", Ok(("", Range::Single(1))));
+    test_range_zero_based!(parse_zero_based_hex_zero, "0x0", Ok(("", Range::Single(1))));
+    test_range_zero_based!(
+        parse_zero_based_interval,
+        "0,2",
+        Ok(("", Range::Interval(1, 3)))
+    );
+    test_range_zero_based!(
+        parse_zero_based_interval_left_open,
+        ",2",
+        Ok(("", Range::Interval(1, 3)))
+    );
+    test_range_zero_based!(
+        parse_zero_based_interval_right_open,
+        "3,",
+        Ok(("", Range::Interval(4, u32::MAX)))
+    );
+    test_range_zero_based!(
+        parse_zero_based_relative,
+        "+2",
+        Ok(("", Range::Relative(2)))
+    );
+
+    macro_rules! test_range_display {
+        ($name:ident, $input:expr, $want:expr) => {
+            #[test]
+            fn $name() {
+                let got = $input.to_string();
+                assert_eq!($want, got);
+                assert_eq!(Ok(("", $input)), range(&got));
+            }
+        };
+    }
+
+    test_range_display!(display_single, Range::Single(4), "4");
+    test_range_display!(display_interval, Range::Interval(4, 8), "4,8");
+    test_range_display!(
+        display_interval_left_open,
+        Range::Interval(u32::MIN, 5),
+        ",5"
+    );
+    test_range_display!(
+        display_interval_right_open,
+        Range::Interval(5, u32::MAX),
+        "5,"
+    );
+    test_range_display!(
+        display_interval_all,
+        Range::Interval(u32::MIN, u32::MAX),
+        ","
+    );
+    test_range_display!(display_relative, Range::Relative(3), "+3");
+
+    #[test]
+    fn resolve() {
+        assert_eq!(Range::Single(7), Range::Relative(3).resolve(4));
+        assert_eq!(Range::Single(3), Range::Relative(3).resolve(0));
+        assert_eq!(Range::Interval(1, 2), Range::Interval(1, 2).resolve(4));
+    }
+
+    #[test]
+    fn shift() {
+        assert_eq!(Some(Range::Single(4)), Range::Single(3).shift(1));
+        assert_eq!(Some(Range::Single(3)), Range::Single(3).shift(0));
+        assert_eq!(Some(Range::Single(1)), Range::Single(3).shift(-2));
+        assert_eq!(None, Range::Single(3).shift(-3));
+        assert_eq!(None, Range::Single(u32::MAX - 1).shift(2));
+        assert_eq!(Some(Range::Interval(3, 6)), Range::Interval(1, 4).shift(2));
+        assert_eq!(
+            Some(Range::Interval(u32::MIN, 2)),
+            Range::Interval(u32::MIN, 4).shift(-2)
+        );
+        assert_eq!(
+            Some(Range::Interval(7, u32::MAX)),
+            Range::Interval(5, u32::MAX).shift(2)
+        );
+        assert_eq!(None, Range::Interval(1, 4).shift(-1));
+        assert_eq!(Some(Range::Relative(2)), Range::Relative(2).shift(-5));
+        assert_eq!(None, Range::Single(1).shift(i64::MIN));
+    }
+
+    #[test]
+    fn parse_with_zero() {
+        assert_eq!(Ok(("", Range::Single(0))), range_with_zero("0"));
+        assert_eq!(Ok(("", Range::Interval(0, 2))), range_with_zero("0,2"));
+        assert_eq!(Ok(("", Range::Single(3))), range_with_zero("3"));
+    }
+
+    #[test]
+    fn parse_shifted() {
+        for (input, base, want) in [
+            ("0", 5, Some(Range::Single(5))),
+            ("0,3", 5, Some(Range::Interval(5, 8))),
+            ("0-3", 5, Some(Range::Interval(5, 8))),
+            (",3", 5, Some(Range::Interval(u32::MIN, 8))),
+            ("-3", 5, Some(Range::Interval(u32::MIN, 8))),
+            ("0,", 5, Some(Range::Interval(5, u32::MAX))),
+            (",", 5, Some(Range::Interval(u32::MIN, u32::MAX))),
+            ("2,3", -1, Some(Range::Interval(1, 2))),
+            ("0,3", -1, None),
+            (",3", -1, Some(Range::Interval(u32::MIN, 2))),
+            ("+2", 5, Some(Range::Relative(2))),
+        ] {
+            let (_, (_, got)) = range_shifted(input, base).unwrap();
+            assert_eq!(want, got, "{} {}", input, base);
+        }
+        assert_eq!(
+            Ok(("", (Range::Interval(0, 3), Some(Range::Interval(1, 4))))),
+            range_shifted("0,3", 1)
+        );
+    }
 }
@@ -1,36 +1,146 @@
 use clap::{error::ErrorKind, CommandFactory, Parser};
+use clap_complete::Shell;
 use lisel::index::Type;
-use lisel::select::{Select, SelectError};
+use lisel::lineparse::{intersect_ranges, merge_ranges, range, range_zero_based, Range};
+#[cfg(feature = "parallel")]
+use lisel::parallel::ParSelect;
+use lisel::pattern;
+use lisel::select::{Select, SelectConfig, SelectError};
+use lisel::str::{read_record, rstrip_delimiter, strip_delimiter, strip_newline};
+use rand::rngs::StdRng;
+use rand::{Rng, SeedableRng};
 use regex::Regex;
-use std::fs::File;
-use std::io::{self, BufRead, BufReader};
-use std::mem;
+use serde_json::json;
+use std::collections::{HashSet, VecDeque};
+use std::env;
+use std::fs::{self, File};
+use std::io::{self, BufRead, BufReader, Cursor, IsTerminal, Read};
+use std::path::{Path, PathBuf};
+use std::process;
+use std::time::Duration;
 
 /// Select lines from target by index.
 #[derive(Parser, Debug)]
-#[command(version, about)]
+#[command(
+    version,
+    about,
+    after_help = "Exit status is 0 if any line is selected, 1 if no lines are selected, and 2 if an error occurred."
+)]
 struct Cli {
-    /// Target filenames, accepts one (INDEX) or two filenames (INDEX and TARGET).
+    /// Target filenames, accepts one (INDEX) or more filenames (INDEX and TARGET).
     ///
     /// 2 files:
     /// The first file is INDEX, the second is TARGET.
     ///
+    /// 3 or more files:
+    /// The first file is INDEX, the rest are TARGET.
+    /// INDEX is applied to each TARGET from the beginning, line numbers restart at each TARGET.
+    /// With --continuous, TARGET files are concatenated instead.
+    ///
     /// 1 file:
     /// The file is INDEX, stdin is TARGET.
-    #[arg(value_name = "FILE", num_args = 1..=2, verbatim_doc_comment)]
+    /// With --stdin-is-index or --swap-file-role, stdin is INDEX, the file is TARGET.
+    ///
+    /// With --index-line-number, INDEX can be a directory:
+    /// the union of the indexes of the regular files in it is used.
+    ///
+    /// Modes without INDEX, such as --each-anchor and --head, accept zero or one filename (TARGET),
+    /// stdin is TARGET when omitted.
+    /// So does --lines, which gives the content of INDEX.
+    #[arg(value_name = "FILE", num_args = 1.., verbatim_doc_comment)]
     files: Vec<String>,
+    /// Read INDEX and TARGET from one file, INDEX before the first line equal to MARKER and TARGET after it.
+    ///
+    /// Accepts zero or one filename, stdin is read when omitted.
+    #[arg(long, value_name = "MARKER", allow_hyphen_values = true, conflicts_with_all = ["index_file", "target_file", "swap_file_role", "continuous", "lines", "mode"], verbatim_doc_comment)]
+    split_marker: Option<String>,
+    /// Concatenate multiple TARGET files and number their lines continuously,
+    /// instead of applying INDEX to each of them.
+    #[arg(long, conflicts_with_all = ["swap_file_role", "index_file", "target_file", "lines", "mode"], verbatim_doc_comment)]
+    continuous: bool,
+    /// INDEX filename, takes precedence over FILE.
+    ///
+    /// stdin is INDEX when only --target-file is given.
+    /// With --index-line-number, can be given multiple times:
+    /// the union of the indexes of the files is used.
+    #[arg(long, value_name = "FILE", conflicts_with_all = ["swap_file_role", "lines", "mode"], verbatim_doc_comment)]
+    index_file: Vec<String>,
+    /// Select only lines also selected by the line number index in FILE, can be given multiple times.
+    ///
+    /// INDEX is intersected with the indexes of all the files.
+    #[arg(long, value_name = "FILE", requires = "index_line_number", conflicts_with_all = ["lines", "mode"], verbatim_doc_comment)]
+    intersect_index: Vec<String>,
+    /// TARGET filename, takes precedence over FILE.
+    ///
+    /// stdin is TARGET when only --index-file is given.
+    #[arg(long, value_name = "FILE", conflicts_with_all = ["swap_file_role", "lines", "mode"], verbatim_doc_comment)]
+    target_file: Option<String>,
     /// Swap file role: INDEX and TARGET.
-    #[arg(short, long)]
+    #[arg(short, long, visible_alias = "invert-file-role")]
     swap_file_role: bool,
+    /// Read INDEX from stdin and TARGET from the only FILE.
+    ///
+    /// Same as --swap-file-role with one FILE, but names the role of stdin explicitly:
+    ///
+    ///   lisel FILE                   FILE is INDEX, stdin is TARGET
+    ///   lisel --stdin-is-index FILE  stdin is INDEX, FILE is TARGET
+    ///   lisel --swap-file-role FILE  stdin is INDEX, FILE is TARGET
+    #[arg(long, conflicts_with_all = ["swap_file_role", "index_file", "target_file", "split_marker", "continuous", "lines", "mode"], verbatim_doc_comment)]
+    stdin_is_index: bool,
+    /// Read INDEX from the environment variable VAR, all files are TARGET.
+    ///
+    /// \n in the value is a line break, e.g. LISEL_INDEX='1\n3'.
+    #[arg(long, value_name = "VAR", conflicts_with_all = ["swap_file_role", "index_file", "target_file", "split_marker", "stdin_is_index", "lines", "mode"], verbatim_doc_comment)]
+    index_env: Option<String>,
     /// Regular expression to determine whether the index of the row exists.
     ///
     /// When a certain line in INDEX matches, output the TARGET line corresponding to that line number.
+    /// Can be given multiple times, a line matches if any of them matches.
     /// Default: .+
-    #[arg(short = 'e', long, value_parser = Regex::new, verbatim_doc_comment)]
-    index_regex: Option<Regex>,
+    #[arg(short = 'e', long, value_parser = pattern::Regex::new, verbatim_doc_comment)]
+    index_regex: Vec<pattern::Regex>,
+    /// Regular expression that all must match the INDEX line, can be given multiple times.
+    #[arg(long, value_name = "RE", value_parser = pattern::Regex::new, conflicts_with_all = ["index_regex"], verbatim_doc_comment)]
+    index_regex_all: Vec<pattern::Regex>,
+    /// Read the regular expression of --index-regex from the first line of the file.
+    #[arg(long, value_name = "FILE", conflicts_with_all = ["index_regex", "index_regex_all", "index_line_number", "lines", "mode"])]
+    regex_file: Option<String>,
+    /// Match the regular expression of the index against field N of INDEX lines instead of the whole line.
+    ///
+    /// Fields are separated by --index-delimiter, a missing field is empty.
+    #[arg(long, value_name = "N", value_parser = clap::value_parser!(u32).range(1..), conflicts_with_all = ["index_line_number", "lines", "mode"], verbatim_doc_comment)]
+    index_field: Option<u32>,
+    /// Separator of fields of INDEX lines for --index-field.
+    ///
+    /// CHAR is an ASCII character or one of \0, \t and \n.
+    #[arg(long, value_name = "CHAR", value_parser = parse_delimiter, default_value = "\\t", requires = "index_field", verbatim_doc_comment)]
+    index_delimiter: u8,
+    /// Fail when matching an INDEX line against the regular expression of the index takes longer than MS milliseconds.
+    ///
+    /// Guards against patterns with catastrophic backtracking in the fancy engine.
+    /// The default engine matches in linear time and never times out.
+    #[arg(long, value_name = "MS", value_parser = clap::value_parser!(u64).range(1..), conflicts_with_all = ["index_line_number", "lines", "mode", "capture_line", "explain"], verbatim_doc_comment)]
+    regex_timeout: Option<u64>,
+    /// Wrap back to the first INDEX line at the end of INDEX,
+    /// repeating the selection pattern of INDEX over a longer TARGET.
+    ///
+    /// INDEX is read into memory.
+    #[arg(long, conflicts_with_all = ["index_line_number", "lines", "mode", "capture_line", "explain", "rest"], verbatim_doc_comment)]
+    cycle_index: bool,
+    /// Select only when the regular expression of the index matches the whole INDEX line.
+    #[arg(short = 'x', long, conflicts_with_all = ["index_line_number", "lines", "mode"])]
+    line_regexp: bool,
     /// Reverse lines to output and lines not to output.
+    ///
+    /// Same as --invert-regex and --invert-range.
     #[arg(short = 'v', long)]
     index_invert_match: bool,
+    /// Reverse lines to output and lines not to output, only for the regular expression index.
+    #[arg(long)]
+    invert_regex: bool,
+    /// Reverse lines to output and lines not to output, only for the line number index.
+    #[arg(long)]
+    invert_range: bool,
     /// Use line number index.
     ///
     /// Instead of selecting rows from INDEX with regular expression, use a line in the following format as index.
@@ -51,293 +161,4477 @@ struct Cli {
     ///
     /// selects lines the beginning of TARGET to LINE_END of TARGET.
     ///
+    ///   ,
+    ///
+    /// selects all lines of TARGET.
+    ///
+    ///   +OFFSET
+    ///
+    /// selects the line OFFSET lines after the last line selected by the previous lines, or line OFFSET at first.
+    ///
+    /// Intervals can also be written with a dash instead of the comma: LINE_START-LINE_END, LINE_START- and -LINE_END.
+    /// Digits of line numbers can be separated by underscores, e.g. 1_000.
+    /// Line numbers prefixed with 0x are hexadecimal, e.g. 0x10 is line 16.
+    ///
     /// LINE_NUMBER and LINE_START are greater than the LINE_NUMBER and LINE_END of previous lines in the INDEX file.
     ///
+    /// Empty lines and lines starting with # are ignored.
+    /// An INDEX without ranges selects no lines, or all lines with --invert-range.
+    ///
     /// Debug logging can be enabled via RUST_LOG in env_logger https://crates.io/crates/env_logger.
-    #[arg(short = 'n', long, conflicts_with_all = ["index_regex"], verbatim_doc_comment)]
+    #[arg(short = 'n', long, conflicts_with_all = ["index_regex", "index_regex_all"], verbatim_doc_comment)]
     index_line_number: bool,
+    /// Add N to the line numbers of the line number index, N can be negative.
+    ///
+    /// For example, --index-base 1 reads an index whose line numbers start from 0,
+    /// and --index-base -1 skips a header line of TARGET.
+    /// Line numbers shifted below 1 are errors.
+    /// Not available with multiple --index-file or a directory INDEX.
+    #[arg(long, value_name = "N", default_value_t = 0, allow_negative_numbers = true, conflicts_with_all = ["index_regex", "index_regex_all", "regex_file", "lines", "mode", "json_index", "intersect_index", "explain"], verbatim_doc_comment)]
+    index_base: i64,
+    /// INDEX is a JSON array of the line numbers of TARGET to select, such as [1,3,5].
+    ///
+    /// The line numbers are positive integers in any order.
+    #[arg(long, conflicts_with_all = ["index_regex", "index_regex_all", "regex_file", "zero_based", "lines", "mode"], verbatim_doc_comment)]
+    json_index: bool,
+    /// Line numbers of the line number index start from 0 instead of 1.
+    ///
+    /// 0 selects the first line of TARGET.
+    #[arg(long, requires = "index_line_number")]
+    zero_based: bool,
+    /// Warn of the ranges of the line number index starting after the end of TARGET, which select nothing.
+    ///
+    /// The warning is printed to stderr unless RUST_LOG disables it.
+    #[arg(long, conflicts_with_all = ["index_regex", "index_regex_all", "regex_file", "mode", "explain"], verbatim_doc_comment)]
+    warn_overshoot: bool,
+    /// Reject intervals of the line number index whose LINE_START is greater than LINE_END.
+    ///
+    /// By default such intervals select nothing.
+    #[arg(long, verbatim_doc_comment)]
+    strict_range: bool,
+    /// Select TARGET lines in blocks of INDEX lines from a line matching START to the next line matching END,
+    /// like awk's /START/,/END/.
+    ///
+    /// Both ends are inclusive, the line matching START can also match END.
+    /// A block without END continues to the end of INDEX. There can be multiple blocks.
+    #[arg(long, num_args = 2, value_names = ["START", "END"], value_parser = pattern::Regex::new, conflicts_with_all = ["index_regex", "index_regex_all", "regex_file", "index_line_number", "lines", "mode", "from_match"], verbatim_doc_comment)]
+    between: Vec<pattern::Regex>,
+    /// Select all TARGET lines from the first line whose INDEX line matches the index regular expression.
+    ///
+    /// INDEX lines after the first match are not used.
+    #[arg(long, conflicts_with_all = ["index_line_number", "lines", "mode", "index_invert_match", "invert_regex", "invert_range"], verbatim_doc_comment)]
+    from_match: bool,
+    /// Select the TARGET line whose line number is captured by GROUP of the index regular expression,
+    /// instead of the line at the same position as the matching INDEX line.
+    ///
+    /// GROUP is the number or the name of a capture group.
+    /// Lines are printed in the order of INDEX, TARGET is read into memory.
+    /// INDEX lines whose capture is not a line number of TARGET are ignored.
+    #[arg(long, value_name = "GROUP", conflicts_with_all = ["index_regex_all", "index_field", "index_line_number", "lines", "mode", "between", "from_match", "rest", "index_invert_match", "invert_regex", "invert_range", "show_index", "count_unselected", "progress_lines", "explain"], verbatim_doc_comment)]
+    capture_line: Option<String>,
+    /// Select all TARGET lines after the last range of the line number index.
+    ///
+    /// With --invert-range, the lines are not selected instead.
+    /// Requires the line number index, such as --index-line-number or --lines.
+    #[arg(long, conflicts_with_all = ["index_regex", "index_regex_all", "regex_file", "mode"], verbatim_doc_comment)]
+    rest: bool,
+    /// Stop at the end of INDEX even when the selection is inverted.
+    ///
+    /// By default, inverted selection accepts all TARGET lines after the end of INDEX,
+    /// since INDEX does not select them.
+    /// With this flag, TARGET lines without INDEX lines paired are not selected,
+    /// so only the lines INDEX explicitly rejects are selected.
+    #[arg(long, conflicts_with_all = ["rest", "mode"], verbatim_doc_comment)]
+    no_accept_after_index: bool,
+    /// Line number index given on the command line instead of INDEX.
+    ///
+    /// Each EXPR is a line of INDEX in the format of --index-line-number, which is implied.
+    /// Can be given multiple times.
+    #[arg(short = 'L', long, value_name = "EXPR", visible_alias = "index-arg", conflicts_with_all = ["swap_file_role", "index_regex", "index_regex_all", "mode"], verbatim_doc_comment)]
+    lines: Vec<String>,
+    /// Prefix each output line with its position within the current group.
+    ///
+    /// A group is a run of consecutive TARGET lines, the position starts at 1 and resets at each gap.
+    #[arg(long)]
+    group_ordinal: bool,
+    /// Prefix each output line with its position in the output, starting at 1.
+    ///
+    /// Unlike the TARGET line number, the position does not skip over unselected lines.
+    /// Printed before the --group-ordinal prefix when both are given.
+    #[arg(long, conflicts_with_all = ["json"], verbatim_doc_comment)]
+    output_number: bool,
+    /// Select TARGET lines matching the regular expression and the lines around them.
+    ///
+    /// The regular expression is matched against TARGET lines instead of INDEX lines, INDEX is not used.
+    /// Overlapping windows are merged, so each line is printed at most once.
+    #[arg(long, value_name = "RE", value_parser = Regex::new, group = "mode", conflicts_with_all = ["swap_file_role", "index_regex", "index_regex_all", "index_invert_match", "invert_regex", "invert_range", "index_line_number"], verbatim_doc_comment)]
+    each_anchor: Option<Regex>,
+    /// Number of lines before each anchor to select.
+    #[arg(long, value_name = "B", default_value_t = 0, requires = "each_anchor")]
+    window_before: usize,
+    /// Number of lines after each anchor to select.
+    #[arg(long, value_name = "A", default_value_t = 0, requires = "each_anchor")]
+    window_after: usize,
+    /// Highlight the parts of output lines matched by the regular expression.
+    ///
+    /// Only for --each-anchor and --target-regex, whose regular expressions are matched against TARGET lines;
+    /// the index regular expressions are matched against INDEX lines, so output lines are not highlighted.
+    /// WHEN is auto, always or never, --color alone is auto.
+    /// auto does not highlight when the NO_COLOR environment variable is set to a non-empty value.
+    #[arg(long, value_name = "WHEN", value_enum, num_args = 0..=1, default_value = "never", default_missing_value = "auto", require_equals = true, verbatim_doc_comment)]
+    color: ColorWhen,
+    /// Select TARGET lines matching the regular expression, like grep.
+    ///
+    /// The regular expression is matched against TARGET lines instead of INDEX lines, INDEX is not used.
+    /// --index-invert-match and --invert-regex select TARGET lines not matching.
+    #[arg(long, value_name = "RE", value_parser = pattern::Regex::new, group = "mode", conflicts_with_all = ["swap_file_role", "index_regex", "index_regex_all", "regex_file", "invert_range", "index_line_number", "lines"], verbatim_doc_comment)]
+    target_regex: Option<pattern::Regex>,
+    /// Drop the selected TARGET lines matching the regular expression.
+    ///
+    /// Applied to the content of TARGET lines after selection by INDEX, independently of the index regular expression.
+    #[arg(long, value_name = "RE", value_parser = Regex::new, conflicts_with_all = ["each_anchor", "two_column", "byte_span", "sample", "validate"], verbatim_doc_comment)]
+    exclude_regex: Option<Regex>,
+    /// Keep only the selected TARGET lines matching the regular expression.
+    ///
+    /// Applied like --exclude-regex, lines matching both are dropped.
+    #[arg(long, value_name = "RE", value_parser = Regex::new, conflicts_with_all = ["each_anchor", "two_column", "byte_span", "sample", "validate"], verbatim_doc_comment)]
+    keep_regex: Option<Regex>,
+    /// Select every Nth line of TARGET, i.e. lines N, 2N, 3N, ..., INDEX is not used.
+    #[arg(long, value_name = "N", value_parser = clap::value_parser!(u32).range(1..), group = "mode", conflicts_with_all = ["swap_file_role", "index_regex", "index_regex_all", "regex_file", "index_line_number", "lines"])]
+    every: Option<u32>,
+    /// Select each line of TARGET independently with probability P (0 to 1), INDEX is not used.
+    #[arg(long, value_name = "P", value_parser = parse_probability, group = "mode", conflicts_with_all = ["swap_file_role", "index_regex", "index_regex_all", "regex_file", "index_invert_match", "invert_regex", "invert_range", "index_line_number", "lines"])]
+    sample: Option<f64>,
+    /// Seed of the random number generator for --sample, the same seed selects the same lines.
+    #[arg(long, value_name = "S", requires = "sample")]
+    seed: Option<u64>,
+    /// Select the first N lines of TARGET, INDEX is not used.
+    #[arg(long, value_name = "N", value_parser = clap::value_parser!(u32).range(1..), group = "mode", conflicts_with_all = ["swap_file_role", "index_regex", "index_regex_all", "regex_file", "index_line_number", "lines"])]
+    head: Option<u32>,
+    /// Select the last N lines of TARGET, INDEX is not used.
+    ///
+    /// TARGET is read into memory to count its lines.
+    #[arg(long, value_name = "N", value_parser = clap::value_parser!(u32).range(1..), group = "mode", conflicts_with_all = ["swap_file_role", "index_regex", "index_regex_all", "regex_file", "index_line_number", "lines"], verbatim_doc_comment)]
+    tail: Option<u32>,
+    /// Select the first PCT percent of lines of TARGET, rounded up, INDEX is not used.
+    ///
+    /// TARGET is read into memory to count its lines.
+    #[arg(long, value_name = "PCT", value_parser = clap::value_parser!(u8).range(0..=100), group = "mode", conflicts_with_all = ["swap_file_role", "index_regex", "index_regex_all", "regex_file", "index_line_number", "lines"], verbatim_doc_comment)]
+    head_percent: Option<u8>,
+    /// Report TARGET lines matching the regular expression next to the line following them.
+    ///
+    /// Each line of the report is the matching line, a tab, and the next TARGET line if it does not match:
+    ///
+    ///   MATCHING_LINE<TAB>NEXT_LINE
+    ///
+    /// The right column is empty when the next line also matches or the matching line is the last line.
+    /// The regular expression is matched against TARGET lines, INDEX is not used.
+    #[arg(long, value_name = "RE", value_parser = Regex::new, group = "mode", conflicts_with_all = ["swap_file_role", "index_regex", "index_regex_all", "index_invert_match", "invert_regex", "invert_range", "index_line_number", "json", "group_ordinal", "unique", "unique_global", "progress_lines", "output_number", "squeeze_blank", "group_separator", "output_separator", "output_terminator"], verbatim_doc_comment)]
+    two_column: Option<Regex>,
+    /// Print bytes of TARGET from byte offset START to END inclusive.
+    ///
+    /// Offsets are 0-based. This is not line oriented: the span can begin and end in the middle of lines,
+    /// and the bytes are printed as they are.
+    /// INDEX is not used.
+    #[arg(long, value_name = "START,END", value_parser = parse_byte_span, group = "mode", conflicts_with_all = ["swap_file_role", "index_regex", "index_regex_all", "index_invert_match", "invert_regex", "invert_range", "index_line_number", "json", "group_ordinal", "unique", "unique_global", "progress_lines", "end_marker"], verbatim_doc_comment)]
+    byte_span: Option<ByteSpan>,
+    /// Check the line number index in INDEX and exit, TARGET is not read.
+    ///
+    /// Each problem is reported to stderr with its line number:
+    /// lines failing to parse, empty lines,
+    /// and ranges not in ascending order, which the streaming selection does not expect.
+    /// The exit status is 2 if any problem is found.
+    #[arg(long, value_name = "INDEX", group = "mode", conflicts_with_all = ["files", "index_file", "target_file", "swap_file_role", "index_regex", "index_regex_all", "regex_file", "index_invert_match", "invert_regex", "invert_range"], verbatim_doc_comment)]
+    validate: Option<String>,
+    /// Print what TARGET lines each INDEX line selects instead of the lines, TARGET is not read.
+    ///
+    ///   index line 2 -> target lines 3..4
+    ///
+    /// Relative ranges are resolved against the end of the previous range.
+    #[arg(long, conflicts_with_all = ["lines", "mode", "between", "from_match", "rest", "index_invert_match", "invert_regex", "invert_range", "count_unselected", "quiet"], verbatim_doc_comment)]
+    explain: bool,
+    /// Skip blank TARGET lines, they are never printed and do not count toward line numbers.
+    ///
+    /// Line 2 of INDEX is the second non-blank line of TARGET,
+    /// and INDEX lines are paired only with non-blank TARGET lines.
+    #[arg(long, conflicts_with_all = ["tail", "head_percent", "each_anchor", "two_column", "byte_span", "sample", "validate", "capture_line", "output_order"], verbatim_doc_comment)]
+    skip_blank_target: bool,
+    /// Order of the output lines.
+    ///
+    /// target: in the order of TARGET, each line is printed at most once.
+    /// index: in the order of the ranges in the line number index, including repeats.
+    ///        TARGET is read into memory.
+    #[arg(long, value_name = "ORDER", value_enum, default_value = "target", conflicts_with_all = ["mode", "between", "from_match", "rest", "index_invert_match", "invert_regex", "invert_range", "count_unselected", "explain", "index_base"], verbatim_doc_comment)]
+    output_order: OutputOrder,
+    /// Suppress a TARGET line printed again right after itself,
+    /// e.g. by overlapping ranges with --output-order=index.
+    ///
+    /// Lines are the same when their TARGET line numbers are, not their contents.
+    #[arg(short, long, verbatim_doc_comment)]
+    unique: bool,
+    /// Suppress a TARGET line printed again anywhere in the output, see --unique.
+    #[arg(long)]
+    unique_global: bool,
+    /// Prefix each output line with the byte offset of its start in TARGET, like grep -b.
+    ///
+    ///   OFFSET:LINE
+    #[arg(short = 'b', long, conflicts_with_all = ["json", "mode"], verbatim_doc_comment)]
+    byte_offset: bool,
+    /// Suppress blank output lines following a blank output line, like cat -s.
+    #[arg(long)]
+    squeeze_blank: bool,
+    /// Print JSON Lines instead of raw lines.
+    ///
+    /// Each output line is an object with the TARGET line number and the content without newline:
+    ///
+    ///   {"line":12,"text":"..."}
+    #[arg(long, conflicts_with_all = ["group_ordinal"], verbatim_doc_comment)]
+    json: bool,
+    /// Print the INDEX line before each output line, separated by a tab.
+    ///
+    /// Only meaningful with the regular expression index,
+    /// where the INDEX line is the one matched to select the TARGET line.
+    #[arg(long, conflicts_with_all = ["json", "index_line_number", "lines", "mode"], verbatim_doc_comment)]
+    show_index: bool,
+    /// Print the number of TARGET lines not selected instead of the selected lines.
+    #[arg(long, conflicts_with_all = ["json", "group_ordinal", "unique", "unique_global", "show_index", "fields", "chars", "each_anchor", "two_column", "byte_span", "sample"])]
+    count_unselected: bool,
+    /// Print nothing, exit with 0 as soon as a line is selected, or with 1 if no lines are selected.
+    #[arg(short = 'q', long, conflicts_with_all = ["json", "count_unselected", "each_anchor", "two_column", "byte_span", "sample", "validate"])]
+    quiet: bool,
+    /// Print the numbers of lines read from TARGET and INDEX and output lines to stderr at the end.
+    ///
+    ///   target=N index=N emitted=N
+    #[arg(long, conflicts_with_all = ["each_anchor", "two_column", "byte_span"], verbatim_doc_comment)]
+    stats: bool,
+    /// Terminator of lines of TARGET and INDEX instead of newline.
+    ///
+    /// BYTE is an ASCII character or one of \0, \t and \n.
+    /// Output lines keep their terminators.
+    #[arg(long, value_name = "BYTE", value_parser = parse_delimiter, default_value = "\\n", conflicts_with_all = ["each_anchor", "two_column", "byte_span"], verbatim_doc_comment)]
+    input_delimiter: u8,
+    /// Lines of TARGET and INDEX are terminated by NUL instead of newline, and so are output lines, like grep -z.
+    #[arg(short = 'Z', long, conflicts_with_all = ["input_delimiter", "json", "each_anchor", "two_column", "byte_span"])]
+    null_data: bool,
+    /// Fail when a line of TARGET or INDEX exceeds N bytes without its terminator,
+    /// instead of reading the whole line into memory.
+    ///
+    /// Unlimited by default.
+    #[arg(long, value_name = "N", value_parser = clap::value_parser!(u64).range(1..), conflicts_with_all = ["each_anchor", "two_column", "byte_span", "sample", "validate", "tail", "head_percent"], verbatim_doc_comment)]
+    max_line_bytes: Option<u64>,
+    /// Output only these fields of the selected lines, like cut.
+    ///
+    /// LIST is comma separated field numbers starting from 1, e.g. 1,3.
+    #[arg(short = 'f', long, value_name = "LIST", value_parser = parse_fields, conflicts_with_all = ["mode"], verbatim_doc_comment)]
+    fields: Option<Fields>,
+    /// Output only these characters of the selected lines.
+    ///
+    /// START,END is in the format of --index-line-number, counting characters from 1.
+    /// Lines shorter than START become empty.
+    #[arg(long, value_name = "START,END", value_parser = parse_chars, conflicts_with_all = ["mode"], verbatim_doc_comment)]
+    chars: Option<Range>,
+    /// Remove trailing whitespace from the selected lines, keeping their terminators.
+    #[arg(long, conflicts_with_all = ["mode"])]
+    trim: bool,
+    /// Remove leading whitespace from the selected lines.
+    #[arg(long, conflicts_with_all = ["mode"])]
+    trim_start: bool,
+    /// Remove leading and trailing whitespace from the selected lines, same as --trim and --trim-start.
+    #[arg(long, conflicts_with_all = ["mode"])]
+    trim_both: bool,
+    /// Field delimiter for --fields.
+    #[arg(
+        short = 'd',
+        long,
+        value_name = "CHAR",
+        default_value_t = '\t',
+        requires = "fields"
+    )]
+    delimiter: char,
+    /// Print a progress line every K TARGET lines read, interleaved with the output.
+    ///
+    /// Progress lines look like:
+    ///
+    ///   # processed 1000 lines
+    ///
+    /// Consumers must skip lines starting with --progress-prefix.
+    /// Not available for records other than lines.
+    #[arg(long, value_name = "K", value_parser = clap::value_parser!(u64).range(1..), conflicts_with_all = ["json", "null_data", "input_delimiter"], verbatim_doc_comment)]
+    progress_lines: Option<u64>,
+    /// Prefix of progress lines.
+    #[arg(long, value_name = "STRING", default_value = "# ")]
+    progress_prefix: String,
+    /// Print STRING as a line after all output, to let consumers detect completion.
+    ///
+    /// Not printed when an error occurs.
+    #[arg(long, value_name = "STRING")]
+    end_marker: Option<String>,
+    /// Print STRING as a line between output lines whose TARGET line numbers are not consecutive.
+    ///
+    /// --group-separator alone is --group-separator=--.
+    #[arg(long, value_name = "STRING", num_args = 0..=1, default_missing_value = "--", require_equals = true, conflicts_with_all = ["json", "count_unselected", "byte_span"], verbatim_doc_comment)]
+    group_separator: Option<String>,
+    /// Join output lines with STRING instead of terminating each line.
+    ///
+    /// The line terminator of each output line is removed,
+    /// and --output-terminator follows the last line.
+    #[arg(long, value_name = "STRING", allow_hyphen_values = true, conflicts_with_all = ["json", "count_unselected", "group_separator", "progress_lines", "byte_span"], verbatim_doc_comment)]
+    output_separator: Option<String>,
+    /// Print STRING after the last output line joined by --output-separator, newline by default.
+    ///
+    /// Given alone, output lines are joined with newline.
+    #[arg(long, value_name = "STRING", allow_hyphen_values = true, conflicts_with_all = ["json", "count_unselected", "group_separator", "progress_lines", "byte_span"], verbatim_doc_comment)]
+    output_terminator: Option<String>,
+    /// Print completion script for SHELL and exit.
+    #[arg(long, value_name = "SHELL", hide = true)]
+    generate_completions: Option<Shell>,
+    /// Print man page in roff and exit.
+    #[arg(long, hide = true)]
+    man: bool,
 }
 
 fn main() {
-    env_logger::init();
     let cli = Cli::parse();
-    if let Err(r) = run(&cli) {
-        let mut cmd = Cli::command();
-        cmd.error(r.0, r.1).exit();
+    env_logger::Builder::from_env(env_logger::Env::default().default_filter_or("warn")).init();
+    match run(&cli) {
+        Ok(true) => {}
+        Ok(false) => process::exit(1),
+        Err(r) => {
+            let mut cmd = Cli::command();
+            cmd.error(r.0, r.1).exit();
+        }
+    }
+}
+
+impl Cli {
+    /// Whether the selected TARGET line passes --keep-regex and --exclude-regex.
+    fn keeps(&self, line: &str) -> bool {
+        let content = strip_newline(line);
+        self.keep_regex.as_ref().is_none_or(|x| x.is_match(content))
+            && !self
+                .exclude_regex
+                .as_ref()
+                .is_some_and(|x| x.is_match(content))
+    }
+
+    /// Whether output lines are joined by --output-separator or --output-terminator.
+    fn joins_output(&self) -> bool {
+        self.output_separator.is_some() || self.output_terminator.is_some()
+    }
+
+    /// Terminator of lines of TARGET and INDEX.
+    fn record_delimiter(&self) -> u8 {
+        if self.null_data {
+            b'\0'
+        } else {
+            self.input_delimiter
+        }
     }
 }
 
 #[derive(Debug)]
 struct RunError(ErrorKind, String);
 
-fn run(cli: &Cli) -> Result<(), RunError> {
-    let index_type = new_index_type(cli.index_regex.clone(), cli.index_line_number);
+impl From<SelectError> for RunError {
+    fn from(x: SelectError) -> Self {
+        RunError(
+            match x {
+                SelectError::Io(_) => ErrorKind::Io,
+                SelectError::Parse(_) => ErrorKind::InvalidValue,
+            },
+            x.to_string(),
+        )
+    }
+}
 
-    match cli.files.as_slice() {
-        [f1, f2] => {
-            let mut index_file = f1;
-            let mut target_file = f2;
+/// Returns whether any line is selected.
+fn run(cli: &Cli) -> Result<bool, RunError> {
+    if let Some(shell) = cli.generate_completions {
+        let mut cmd = Cli::command();
+        let name = cmd.get_name().to_string();
+        clap_complete::generate(shell, &mut cmd, name, &mut io::stdout());
+        return Ok(true);
+    }
+    if cli.man {
+        return clap_mangen::Man::new(Cli::command())
+            .render(&mut io::stdout())
+            .map(|_| true)
+            .map_err(|x| RunError(ErrorKind::Io, x.to_string()));
+    }
+
+    if let Some(name) = &cli.validate {
+        return validate(cli, name);
+    }
+    if let Some(re) = &cli.each_anchor {
+        return each_anchor(cli, re);
+    }
+    if let Some(re) = &cli.two_column {
+        return two_column(cli, re);
+    }
+    if let Some(p) = cli.sample {
+        return sample(cli, p);
+    }
+    if let Some(span) = &cli.byte_span {
+        return byte_span(cli, span);
+    }
+    if let Some(group) = &cli.capture_line {
+        return capture_line(cli, group);
+    }
+
+    let index_type = if let Some(re) = &cli.target_regex {
+        Some(Type::Re(re.clone()))
+    } else if let Some(n) = cli.every {
+        Some(Type::Every(n))
+    } else if let [start, _] = cli.between.as_slice() {
+        line_regexps(vec![start.clone()], cli.line_regexp)?
+            .pop()
+            .map(Type::Re)
+    } else if !cli.index_regex_all.is_empty() {
+        Some(Type::ReAll(line_regexps(
+            cli.index_regex_all.clone(),
+            cli.line_regexp,
+        )?))
+    } else {
+        let index_regex = match &cli.regex_file {
+            Some(f) => vec![read_regex_file(f)?],
+            None => cli.index_regex.clone(),
+        };
+        let index_regex = line_regexps(index_regex, cli.line_regexp)?;
+        new_index_type(
+            index_regex,
+            cli.index_line_number
+                || cli.json_index
+                || !cli.lines.is_empty()
+                || cli.head.is_some()
+                || cli.tail.is_some()
+                || cli.head_percent.is_some(),
+        )?
+    };
+    if cli.rest && index_type.is_some() {
+        return Err(RunError(
+            ErrorKind::ArgumentConflict,
+            "--rest requires a line number index".to_string(),
+        ));
+    }
+    let block_end = match cli.between.as_slice() {
+        [_, end] => line_regexps(vec![end.clone()], cli.line_regexp)?.pop(),
+        _ => None,
+    };
+    if cli.explain {
+        return explain(cli, index_type);
+    }
+    if cli.output_order == OutputOrder::Index {
+        if index_type.is_some() {
+            return Err(RunError(
+                ErrorKind::ArgumentConflict,
+                "--output-order=index requires a line number index".to_string(),
+            ));
+        }
+        return index_order(cli);
+    }
 
-            if cli.swap_file_role {
-                mem::swap(&mut target_file, &mut index_file);
+    let mut printer = match &cli.target_regex {
+        Some(re) => Printer::new(cli).highlight(re),
+        None => Printer::new(cli),
+    };
+    let config = SelectConfig {
+        invert_regex: cli.index_invert_match || cli.invert_regex,
+        invert_range: cli.index_invert_match || cli.invert_range,
+        zero_based: cli.zero_based,
+        strict_range: cli.strict_range,
+        rest: cli.rest,
+        accept_after_index: !cli.no_accept_after_index,
+        index_base: cli.index_base,
+        from_match: cli.from_match,
+        block_end,
+        match_target: cli.target_regex.is_some(),
+        delimiter: cli.record_delimiter(),
+        max_line_bytes: cli.max_line_bytes.map(|x| x as usize),
+        index_field: cli.index_field.map(|x| x as usize),
+        index_delimiter: cli.index_delimiter as char,
+        warn_overshoot: cli.warn_overshoot,
+        skip_blank_target: cli.skip_blank_target,
+        regex_timeout: cli.regex_timeout.map(Duration::from_millis),
+        cycle_index: cli.cycle_index,
+        ..SelectConfig::new(index_type, false)
+    };
+    let mut selected: u64 = 0;
+    // numbers of lines read from all TARGET and INDEX
+    let mut target_read: u64 = 0;
+    let mut index_read: u64 = 0;
+    for (target, index) in open_streams(cli)? {
+        let mut selector = Selector::new(&config, target, index);
+        while let Some(line) = selector.next() {
+            let mut r = line?;
+            if !cli.keeps(&r) {
+                continue;
             }
+            if cli.quiet {
+                return Ok(true);
+            }
+            selected += 1;
+            if cli.count_unselected {
+                continue;
+            }
+            if let Some(f) = &cli.fields {
+                r = f.cut(&r, cli.delimiter);
+            }
+            if let Some(c) = &cli.chars {
+                r = slice_chars(&r, c);
+            }
+            if cli.trim || cli.trim_start || cli.trim_both {
+                r = trim_line(
+                    &r,
+                    cli.record_delimiter(),
+                    cli.trim_start || cli.trim_both,
+                    cli.trim || cli.trim_both,
+                );
+            }
+            if cli.show_index {
+                r = format!("{}\t{}", selector.index_content(), r);
+            }
+            if cli.byte_offset {
+                r = format!("{}:{}", selector.target_line_offset(), r);
+            }
+            printer.print(selector.target_line(), r);
+        }
+        if cli.count_unselected {
+            selector.skip_rest()?;
+        }
+        printer.next_target(selector.target_line());
+        target_read += selector.target_line();
+        index_read += selector.index_line();
+    }
+    if cli.count_unselected {
+        println!("{}", target_read - selected);
+    }
+    printer.finish(target_read);
+    print_stats(cli, target_read, index_read, printer.emitted);
+    Ok(selected > 0)
+}
+
+/// Print the numbers of lines read from TARGET and INDEX and output lines with --stats.
+fn print_stats(cli: &Cli, target_read: u64, index_read: u64, emitted: u64) {
+    if cli.stats {
+        eprintln!(
+            "target={} index={} emitted={}",
+            target_read, index_read, emitted
+        );
+    }
+}
 
-            let target = File::open(target_file)
-                .map(BufReader::new)
-                .map_err(|x| RunError(ErrorKind::InvalidValue, x.to_string()))?;
-            let index = File::open(index_file)
-                .map(BufReader::new)
-                .map_err(|x| RunError(ErrorKind::InvalidValue, x.to_string()))?;
+fn parse_probability(s: &str) -> Result<f64, String> {
+    match s.parse::<f64>() {
+        Ok(p) if (0.0..=1.0).contains(&p) => Ok(p),
+        Ok(_) => Err("expected a probability from 0 to 1".to_string()),
+        Err(x) => Err(x.to_string()),
+    }
+}
 
-            let selector = Select::new(target, index, index_type, cli.index_invert_match);
+/// Print each TARGET line with the probability, INDEX is not used.
+fn sample(cli: &Cli, p: f64) -> Result<bool, RunError> {
+    let mut target = open_target(cli)?;
+    let mut rng = match cli.seed {
+        Some(s) => StdRng::seed_from_u64(s),
+        None => StdRng::from_entropy(),
+    };
+    let mut printer = Printer::new(cli);
+    let mut linum = 0;
+    loop {
+        let mut line = String::new();
+        let n = read_record(&mut target, cli.record_delimiter(), &mut line)
+            .map_err(|x| RunError(ErrorKind::Io, x.to_string()))?;
+        if n == 0 {
+            break;
+        }
+        linum += 1;
+        if rng.gen_bool(p) {
+            printer.print(linum, line);
+        }
+    }
+    printer.finish(linum);
+    print_stats(cli, linum, 0, printer.emitted);
+    Ok(printer.emitted > 0)
+}
 
-            for line in selector {
-                let r = line.map_err(|x| {
-                    RunError(
-                        match x {
-                            SelectError::Io(_) => ErrorKind::Io,
-                            SelectError::Parse(_) => ErrorKind::InvalidValue,
-                        },
-                        x.to_string(),
-                    )
-                })?;
-                print!("{}", r);
+/// Report problems of the line number index to stderr.
+fn validate(cli: &Cli, name: &str) -> Result<bool, RunError> {
+    let content = fs::read_to_string(name)
+        .map_err(|x| RunError(ErrorKind::Io, format!("{}: {}", name, x)))?;
+    let mut problems = 0;
+    // line number and end of the range reaching furthest so far
+    let mut prev: Option<(usize, u32)> = None;
+    // end of the previous range, the base of relative ranges
+    let mut base = 0;
+    for (i, line) in content.lines().enumerate() {
+        let linum = i + 1;
+        let line = line.trim_end_matches('\r');
+        if line.is_empty() {
+            eprintln!("{}:{}: empty line", name, linum);
+            problems += 1;
+            continue;
+        }
+        if line.trim_start().starts_with('#') {
+            continue;
+        }
+        let t = match parse_range(cli, line) {
+            Ok((_, r)) => Type::Number(r.resolve(base)),
+            Err(x) => {
+                eprintln!("{}:{}: {}: {}", name, linum, line, x);
+                problems += 1;
+                continue;
             }
-            Ok(())
+        };
+        if t.end() != u32::MAX {
+            base = t.end();
         }
-        [f1] => {
-            let stdin = io::stdin();
-            let target_stdin = stdin.lock();
-            let mut target: Box<dyn BufRead> = Box::new(target_stdin);
-            let index_file = File::open(f1)
-                .map(BufReader::new)
-                .map_err(|x| RunError(ErrorKind::InvalidValue, x.to_string()))?;
-            let mut index: Box<dyn BufRead> = Box::new(index_file);
-
-            if cli.swap_file_role {
-                mem::swap(&mut target, &mut index);
+        if t.start() > t.end() {
+            eprintln!("{}:{}: {}: start is greater than end", name, linum, line);
+            problems += 1;
+            continue;
+        }
+        if let Some((p, e)) = prev {
+            if t.start() <= e {
+                eprintln!(
+                    "{}:{}: {}: not in ascending order, overlaps line {}",
+                    name, linum, line, p
+                );
+                problems += 1;
             }
+        }
+        if prev.is_none_or(|(_, e)| e < t.end()) {
+            prev = Some((linum, t.end()));
+        }
+    }
+    if problems > 0 {
+        return Err(RunError(
+            ErrorKind::InvalidValue,
+            format!("{}: {} problems found", name, problems),
+        ));
+    }
+    Ok(true)
+}
 
-            let selector = Select::new(target, index, index_type, cli.index_invert_match);
-
-            for line in selector {
-                let r = line.map_err(|x| {
+/// Print what TARGET lines each INDEX line selects.
+fn explain(cli: &Cli, index_type: Option<Type>) -> Result<bool, RunError> {
+    let mut index = match open_streams(cli)?.into_iter().next() {
+        Some((_, x)) => x,
+        None => return Ok(false),
+    };
+    let mut selected = false;
+    // end of the previous range, the base of relative ranges
+    let mut base = 0;
+    let mut linum = 0;
+    loop {
+        let mut line = String::new();
+        let n = read_record(&mut index, cli.record_delimiter(), &mut line)
+            .map_err(|x| RunError(ErrorKind::Io, x.to_string()))?;
+        if n == 0 {
+            break;
+        }
+        linum += 1;
+        rstrip_delimiter(&mut line, cli.record_delimiter());
+        let target = match &index_type {
+            Some(r) => {
+                let text = match cli.index_field {
+                    Some(n) => line
+                        .split(cli.index_delimiter as char)
+                        .nth(n as usize - 1)
+                        .unwrap_or(""),
+                    None => &line,
+                };
+                if r.select(0, text) {
+                    format!("target line {}", linum)
+                } else {
+                    "no target lines".to_string()
+                }
+            }
+            None if line.is_empty() || line.trim_start().starts_with('#') => "ignored".to_string(),
+            None => {
+                let (_, r) = parse_range(cli, &line).map_err(|x| {
                     RunError(
-                        match x {
-                            SelectError::Io(_) => ErrorKind::Io,
-                            SelectError::Parse(_) => ErrorKind::InvalidValue,
-                        },
-                        x.to_string(),
+                        ErrorKind::InvalidValue,
+                        format!("index line {}: {}: {}", linum, line, x),
                     )
                 })?;
-                print!("{}", r);
+                let t = Type::Number(r.resolve(base));
+                let (start, end) = (t.start().max(1), t.end());
+                if end != u32::MAX {
+                    base = end;
+                }
+                match end {
+                    e if start > e => "no target lines".to_string(),
+                    e if start == e => format!("target line {}", start),
+                    u32::MAX => format!("target lines {}..end", start),
+                    e => format!("target lines {}..{}", start, e),
+                }
             }
-            Ok(())
-        }
-        _ => Err(RunError(
-            ErrorKind::WrongNumberOfValues,
-            "files".to_string(),
-        )),
+        };
+        selected |= target.starts_with("target");
+        println!("index line {} -> {}", linum, target);
     }
+    Ok(selected)
 }
 
-fn new_index_type(r: Option<Regex>, index_line_number: bool) -> Option<Type> {
-    if index_line_number {
-        None
-    } else {
-        r.or_else(|| Some(Regex::new(".+").unwrap())).map(Type::Re)
+/// Print the TARGET lines whose line numbers are captured from the matching INDEX lines.
+fn capture_line(cli: &Cli, group: &str) -> Result<bool, RunError> {
+    let index_regex = match &cli.regex_file {
+        Some(f) => vec![read_regex_file(f)?],
+        None => cli.index_regex.clone(),
+    };
+    if index_regex.is_empty() {
+        return Err(RunError(
+            ErrorKind::MissingRequiredArgument,
+            "--capture-line requires --index-regex or --regex-file".to_string(),
+        ));
+    }
+    // compile again for the captures
+    let index_regex: Vec<Regex> = line_regexps(index_regex, cli.line_regexp)?
+        .iter()
+        .map(|x| Regex::new(x.as_str()))
+        .collect::<Result<_, _>>()
+        .map_err(|x| RunError(ErrorKind::InvalidValue, x.to_string()))?;
+    for re in &index_regex {
+        let exists = match group.parse::<usize>() {
+            Ok(n) => n < re.captures_len(),
+            Err(_) => re.capture_names().any(|x| x == Some(group)),
+        };
+        if !exists {
+            return Err(RunError(
+                ErrorKind::InvalidValue,
+                format!("--capture-line: no capture group {} in {}", group, re),
+            ));
+        }
+    }
+
+    let mut printer = Printer::new(cli);
+    let mut target_read: u64 = 0;
+    let mut index_read: u64 = 0;
+    for (mut target, mut index) in open_streams(cli)? {
+        let mut content = String::new();
+        target
+            .read_to_string(&mut content)
+            .map_err(|x| RunError(ErrorKind::Io, x.to_string()))?;
+        let lines: Vec<&str> = content
+            .split_inclusive(cli.record_delimiter() as char)
+            .collect();
+        target_read += lines.len() as u64;
+        loop {
+            let mut line = String::new();
+            let n = read_record(&mut index, cli.record_delimiter(), &mut line)
+                .map_err(|x| RunError(ErrorKind::Io, x.to_string()))?;
+            if n == 0 {
+                break;
+            }
+            index_read += 1;
+            rstrip_delimiter(&mut line, cli.record_delimiter());
+            let linum = index_regex
+                .iter()
+                .find_map(|re| {
+                    let caps = re.captures(&line)?;
+                    match group.parse::<usize>() {
+                        Ok(n) => caps.get(n),
+                        Err(_) => caps.name(group),
+                    }
+                })
+                .and_then(|x| x.as_str().parse::<usize>().ok());
+            if let Some(n @ 1..) = linum {
+                if let Some(x) = lines.get(n - 1) {
+                    if cli.keeps(x) {
+                        if cli.quiet {
+                            return Ok(true);
+                        }
+                        printer.print(n as u64, x.to_string());
+                    }
+                }
+            }
+        }
     }
+    printer.finish(target_read);
+    print_stats(cli, target_read, index_read, printer.emitted);
+    Ok(printer.emitted > 0)
 }
 
-#[cfg(test)]
-mod tests {
-    use std::fs::File;
-    use std::io::Write;
-    use std::process::{Command, Stdio};
-    use tempfile::TempDir;
+/// Print the TARGET lines in the order of the ranges in the line number index.
+fn index_order(cli: &Cli) -> Result<bool, RunError> {
+    let mut printer = Printer::new(cli);
+    let mut target_read: u64 = 0;
+    let mut index_read: u64 = 0;
+    for (mut target, mut index) in open_streams(cli)? {
+        let mut content = String::new();
+        target
+            .read_to_string(&mut content)
+            .map_err(|x| RunError(ErrorKind::Io, x.to_string()))?;
+        let lines: Vec<&str> = content
+            .split_inclusive(cli.record_delimiter() as char)
+            .collect();
+        target_read += lines.len() as u64;
+        // end of the previous range, the base of relative ranges
+        let mut base = 0;
+        let mut linum = 0;
+        loop {
+            let mut line = String::new();
+            let n = read_record(&mut index, cli.record_delimiter(), &mut line)
+                .map_err(|x| RunError(ErrorKind::Io, x.to_string()))?;
+            if n == 0 {
+                break;
+            }
+            linum += 1;
+            index_read += 1;
+            rstrip_delimiter(&mut line, cli.record_delimiter());
+            if line.is_empty() || line.trim_start().starts_with('#') {
+                continue;
+            }
+            let (_, r) = parse_range(cli, &line).map_err(|x| {
+                RunError(
+                    ErrorKind::InvalidValue,
+                    format!("index line {}: {}: {}", linum, line, x),
+                )
+            })?;
+            let t = Type::Number(r.resolve(base));
+            let (start, end) = (t.start().max(1), t.end());
+            if end != u32::MAX {
+                base = end;
+            }
+            let end = end.min(lines.len() as u32);
+            for n in start..=end {
+                let x = lines[n as usize - 1];
+                if cli.keeps(x) {
+                    if cli.quiet {
+                        return Ok(true);
+                    }
+                    printer.print(u64::from(n), x.to_string());
+                }
+            }
+        }
+    }
+    printer.finish(target_read);
+    print_stats(cli, target_read, index_read, printer.emitted);
+    Ok(printer.emitted > 0)
+}
 
-    macro_rules! test_e2e {
-        ($name:expr, $dir:expr, $bin:expr, $args:expr, $data:expr, $stdin:expr, $want:expr) => {{
-            eprint!("test {} ... ", $name);
+/// Print TARGET lines matching the anchor regex together with the lines around them.
+///
+/// The regex is matched against TARGET lines, INDEX is not used.
+fn each_anchor(cli: &Cli, re: &Regex) -> Result<bool, RunError> {
+    let mut target = open_target(cli)?;
+    let highlight = pattern::Regex::from(re.clone());
+    let mut printer = Printer::new(cli).highlight(&highlight);
+    // preceding lines not printed yet
+    let mut before: VecDeque<(u64, String)> = VecDeque::new();
+    // number of following lines to print
+    let mut after = 0;
+    let mut linum = 0;
+    loop {
+        let mut line = String::new();
+        let n = target
+            .read_line(&mut line)
+            .map_err(|x| RunError(ErrorKind::Io, x.to_string()))?;
+        if n == 0 {
+            break;
+        }
+        linum += 1;
 
-            let f1_path = $dir.path().join(format!("{}_f1", $name));
-            {
-                let mut f1 = File::create(&f1_path).expect("failed to create 1st file");
-                f1.write_all($data.as_bytes())
-                    .expect("failed to write data to 1st file");
+        if re.is_match(strip_newline(&line)) {
+            for (n, x) in before.drain(..) {
+                printer.print(n, x);
+            }
+            printer.print(linum, line);
+            after = cli.window_after;
+        } else if after > 0 {
+            printer.print(linum, line);
+            after -= 1;
+        } else {
+            before.push_back((linum, line));
+            if before.len() > cli.window_before {
+                before.pop_front();
             }
+        }
+    }
+    printer.finish(linum);
+    Ok(printer.emitted > 0)
+}
 
-            let mut args = vec![f1_path.to_str().unwrap()];
-            args.extend_from_slice(&$args);
-            let mut process = Command::new($bin)
-                .args(args.clone())
-                .stdin(Stdio::piped())
-                .stdout(Stdio::piped())
-                .stderr(Stdio::piped())
-                .spawn()
-                .expect("failed to spawn process");
-            if let Some(ref mut stdin) = process.stdin {
-                stdin
-                    .write_all($stdin.as_bytes())
-                    .expect("failed to write data to stdin");
+/// Print TARGET lines matching the regex in the left column and their following lines in the right column.
+fn two_column(cli: &Cli, re: &Regex) -> Result<bool, RunError> {
+    let target = open_target(cli)?;
+    let mut matched_any = false;
+    // matching line waiting for the right column
+    let mut left: Option<String> = None;
+    let mut linum = 0;
+    for line in target.lines() {
+        let line = line.map_err(|x| RunError(ErrorKind::Io, x.to_string()))?;
+        linum += 1;
+        let matched = re.is_match(&line);
+        matched_any |= matched;
+        match left.take() {
+            Some(l) if matched => {
+                println!("{}\t", l);
+                left = Some(line);
             }
+            Some(l) => println!("{}\t{}", l, line),
+            None if matched => left = Some(line),
+            None => {}
+        }
+    }
+    if let Some(l) = left {
+        println!("{}\t", l);
+    }
+    Printer::new(cli).finish(linum);
+    Ok(matched_any)
+}
 
-            let output = process.wait_with_output().expect("failed to wait process");
-            assert!(output.status.success());
+fn parse_delimiter(s: &str) -> Result<u8, String> {
+    match s {
+        "\\0" => Ok(b'\0'),
+        "\\t" => Ok(b'\t'),
+        "\\n" => Ok(b'\n'),
+        x if x.len() == 1 && x.is_ascii() => Ok(x.as_bytes()[0]),
+        _ => Err("expected an ASCII character, \\0, \\t or \\n".to_string()),
+    }
+}
 
-            let got = String::from_utf8(output.stdout).expect("failed to read stdout");
-            let err = String::from_utf8(output.stderr).expect("failed to read stderr");
+fn parse_chars(s: &str) -> Result<Range, String> {
+    match range(s) {
+        Ok(("", Range::Relative(_))) => Err("unexpected relative range".to_string()),
+        Ok(("", r)) => Ok(r),
+        Ok((rest, _)) => Err(format!("unexpected {}", rest)),
+        Err(x) => Err(x.to_string()),
+    }
+}
 
-            assert_eq!(
-                $want, got,
-                "{} stdout, args: {:?} err: {}",
-                $name, &args, err
-            );
+/// Keep only the characters of the line in the range, counting from 1.
+fn slice_chars(line: &str, r: &Range) -> String {
+    let content = strip_newline(line);
+    let t = Type::Number(r.clone());
+    let start = t.start().max(1) as usize;
+    let end = t.end() as usize;
+    let sliced: String = content
+        .chars()
+        .skip(start - 1)
+        .take(end.saturating_sub(start - 1))
+        .collect();
+    format!("{}{}", sliced, &line[content.len()..])
+}
 
-            eprintln!("ok");
-        }};
+/// Remove whitespace from the start and the end of the line, keeping its terminator.
+fn trim_line(line: &str, delimiter: u8, start: bool, end: bool) -> String {
+    let content = strip_delimiter(line, delimiter);
+    let mut trimmed = content;
+    if start {
+        trimmed = trimmed.trim_start();
     }
+    if end {
+        trimmed = trimmed.trim_end();
+    }
+    format!("{}{}", trimmed, &line[content.len()..])
+}
 
-    macro_rules! test_e2e_files {
-        ($name:expr, $dir:expr, $bin:expr, $args:expr, $index:expr, $target:expr, $want:expr) => {{
-            eprint!("test {} ... ", $name);
+/// 1-based field numbers to output.
+#[derive(Debug, Clone)]
+struct Fields(Vec<usize>);
 
-            let f1_path = $dir.path().join(format!("{}_f1", $name));
-            let f2_path = $dir.path().join(format!("{}_f2", $name));
-            {
-                let mut f1 = File::create(&f1_path).expect("failed to create 1st file");
-                let mut f2 = File::create(&f2_path).expect("failed to create 2nd file");
-                f1.write_all($index.as_bytes())
-                    .expect("failed to write index to 1st file");
-                f2.write_all($target.as_bytes())
-                    .expect("failed to write target to 2nd file");
-            }
+fn parse_fields(s: &str) -> Result<Fields, String> {
+    s.split(',')
+        .map(|x| match x.parse::<usize>() {
+            Ok(0) => Err("fields are numbered from 1".to_string()),
+            Ok(n) => Ok(n),
+            Err(e) => Err(format!("{}: {}", x, e)),
+        })
+        .collect::<Result<_, _>>()
+        .map(Fields)
+}
 
-            let mut args = vec![f1_path.to_str().unwrap(), f2_path.to_str().unwrap()];
-            args.extend_from_slice(&$args);
-            let output = Command::new($bin)
-                .args(args.clone())
-                .output()
-                .expect("failed to run process");
-            assert!(
-                output.status.success(),
-                "{} status, args: {:?}",
-                $name,
-                &args
-            );
-            let got = String::from_utf8(output.stdout).expect("failed to read stdout");
-            let err = String::from_utf8(output.stderr).expect("failed to read stderr");
-            assert_eq!(
-                $want, got,
-                "{} stdout, args: {:?} err: {}",
-                $name, &args, err
-            );
+impl Fields {
+    /// Keep only the fields of the line, joined by the delimiter.
+    /// Fields beyond the end of the line are skipped.
+    fn cut(&self, line: &str, delimiter: char) -> String {
+        let content = strip_newline(line);
+        let columns: Vec<&str> = content.split(delimiter).collect();
+        let selected: Vec<&str> = self
+            .0
+            .iter()
+            .filter_map(|&i| columns.get(i - 1).copied())
+            .collect();
+        format!(
+            "{}{}",
+            selected.join(&delimiter.to_string()),
+            &line[content.len()..]
+        )
+    }
+}
 
-            eprintln!("ok");
-        }};
+/// Inclusive range of 0-based byte offsets.
+#[derive(Debug, Clone)]
+struct ByteSpan {
+    start: u64,
+    end: u64,
+}
+
+fn parse_byte_span(s: &str) -> Result<ByteSpan, String> {
+    let (start, end) = s
+        .split_once(',')
+        .ok_or_else(|| "expected START,END".to_string())?;
+    let start: u64 = start.parse().map_err(|x| format!("START: {}", x))?;
+    let end: u64 = end.parse().map_err(|x| format!("END: {}", x))?;
+    if start > end {
+        return Err("START is greater than END".to_string());
     }
+    Ok(ByteSpan { start, end })
+}
 
-    #[test]
-    fn main() {
-        let status = Command::new("cargo")
-            .arg("build")
-            .status()
-            .expect("failed to execute build");
-        assert!(status.success(), "{}", "cargo build");
+/// Print the bytes of TARGET in the span, false if the span is beyond the end of TARGET.
+fn byte_span(cli: &Cli, span: &ByteSpan) -> Result<bool, RunError> {
+    let mut target = open_target(cli)?;
+    let to_err = |x: io::Error| RunError(ErrorKind::Io, x.to_string());
+    io::copy(&mut (&mut target).take(span.start), &mut io::sink()).map_err(to_err)?;
+    let n = io::copy(
+        &mut target.take((span.end - span.start).saturating_add(1)),
+        &mut io::stdout(),
+    )
+    .map_err(to_err)?;
+    Ok(n > 0)
+}
 
-        let bin = "./target/debug/lisel";
-        let output = Command::new(bin)
+#[derive(Debug, Clone, Copy, PartialEq, Eq, clap::ValueEnum)]
+enum OutputOrder {
+    Target,
+    Index,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, clap::ValueEnum)]
+enum ColorWhen {
+    /// Only when stdout is a terminal and NO_COLOR is not set.
+    Auto,
+    Always,
+    Never,
+}
+
+impl ColorWhen {
+    fn enabled(self) -> bool {
+        match self {
+            ColorWhen::Auto => {
+                // https://no-color.org/
+                env::var_os("NO_COLOR").is_none_or(|x| x.is_empty()) && io::stdout().is_terminal()
+            }
+            ColorWhen::Always => true,
+            ColorWhen::Never => false,
+        }
+    }
+}
+
+/// Output of selected TARGET lines.
+struct Printer<'a> {
+    cli: &'a Cli,
+    /// TARGET line number of the previous output line, to detect gaps.
+    prev_linum: Option<u64>,
+    /// Position of the previous output line within its group.
+    ordinal: u64,
+    progress: Progress<'a>,
+    /// Number of lines of the previous TARGET files, for the progress.
+    target_base: u64,
+    /// TARGET line numbers of the output lines, for --unique-global.
+    seen: HashSet<u64>,
+    /// Regular expression to highlight in output lines, for --color.
+    highlight: Option<&'a pattern::Regex>,
+    /// Number of output lines.
+    emitted: u64,
+    /// Whether the last output line lacks a trailing newline,
+    /// i.e. it is the last line of TARGET without newline.
+    unterminated: bool,
+    /// Whether the previous output line is blank, for --squeeze-blank.
+    prev_blank: bool,
+}
+
+impl<'a> Printer<'a> {
+    fn new(cli: &'a Cli) -> Printer<'a> {
+        Printer {
+            cli,
+            prev_linum: None,
+            ordinal: 0,
+            progress: Progress::new(cli),
+            target_base: 0,
+            seen: HashSet::new(),
+            highlight: None,
+            emitted: 0,
+            unterminated: false,
+            prev_blank: false,
+        }
+    }
+
+    /// Highlight the matches of the regex if --color is enabled.
+    fn highlight(mut self, re: &'a pattern::Regex) -> Printer<'a> {
+        if self.cli.color.enabled() {
+            self.highlight = Some(re);
+        }
+        self
+    }
+
+    /// Whether line `linum` of TARGET should be suppressed as a duplicate.
+    fn is_duplicate(&mut self, linum: u64) -> bool {
+        if self.cli.unique_global {
+            return !self.seen.insert(linum);
+        }
+        self.cli.unique && self.prev_linum == Some(linum)
+    }
+
+    /// Print line `linum` of TARGET.
+    fn print(&mut self, linum: u64, mut line: String) {
+        // lines before this line have been processed
+        self.progress.report(self.target_base + linum - 1);
+        if self.is_duplicate(linum) {
+            return;
+        }
+        self.ordinal = match self.prev_linum {
+            Some(p) if p + 1 == linum => self.ordinal + 1,
+            _ => 1,
+        };
+        let cli = self.cli;
+        if let Some(sep) = &cli.group_separator {
+            // between groups, not before the first one
+            if self.ordinal == 1 && self.prev_linum.is_some() {
+                self.print_line(sep);
+                self.prev_blank = false;
+            }
+        }
+        // a squeezed line still belongs to its group
+        self.prev_linum = Some(linum);
+        let blank = strip_delimiter(&line, cli.record_delimiter()).is_empty();
+        if cli.squeeze_blank && blank && self.prev_blank {
+            return;
+        }
+        self.prev_blank = blank;
+        self.emitted += 1;
+
+        if self.cli.json {
+            let mut text = line;
+            rstrip_delimiter(&mut text, cli.record_delimiter());
+            println!("{}", json!({"line": linum, "text": text}));
+            return;
+        }
+        if cli.joins_output() {
+            rstrip_delimiter(&mut line, cli.record_delimiter());
+            if self.emitted > 1 {
+                print!("{}", cli.output_separator.as_deref().unwrap_or("\n"));
+            }
+        } else if self.cli.null_data && !line.ends_with('\0') {
+            line.push('\0');
+        }
+        if self.cli.output_number {
+            print!("{}:", self.emitted);
+        }
+        if self.cli.group_ordinal {
+            print!("{}:", self.ordinal);
+        }
+        self.unterminated = !line.ends_with('\n');
+        match self.highlight {
+            Some(re) => {
+                let content = strip_newline(&line);
+                let mut colored = String::new();
+                let mut last = 0;
+                for m in re
+                    .find_ranges(content)
+                    .into_iter()
+                    .filter(|m| !m.is_empty())
+                {
+                    colored.push_str(&content[last..m.start]);
+                    colored.push_str(&format!("\x1b[01;31m{}\x1b[0m", &content[m.clone()]));
+                    last = m.end;
+                }
+                colored.push_str(&content[last..]);
+                print!("{}{}", colored, &line[content.len()..]);
+            }
+            None => print!("{}", line),
+        }
+    }
+
+    /// Move on to the next TARGET file after `processed` lines of the current one.
+    fn next_target(&mut self, processed: u64) {
+        self.target_base += processed;
+    }
+
+    /// Finish output after `processed` lines of TARGET have been read.
+    fn finish(&mut self, processed: u64) {
+        self.progress.report(processed);
+        if self.cli.joins_output() && self.emitted > 0 {
+            let terminator = self.cli.output_terminator.as_deref().unwrap_or("\n");
+            print!("{}", terminator);
+            self.unterminated = !terminator.ends_with('\n');
+        }
+        if let Some(m) = &self.cli.end_marker {
+            self.print_line(m);
+        }
+    }
+
+    /// Print a line of its own apart from TARGET lines, such as the end marker.
+    fn print_line(&mut self, s: &str) {
+        if self.cli.null_data {
+            print!("{}\0", s);
+            return;
+        }
+        if self.unterminated {
+            println!();
+            self.unterminated = false;
+        }
+        println!("{}", s);
+    }
+}
+
+/// Periodic report of the number of processed TARGET lines.
+struct Progress<'a> {
+    interval: Option<u64>,
+    prefix: &'a str,
+    reported: u64,
+}
+
+impl<'a> Progress<'a> {
+    fn new(cli: &'a Cli) -> Progress<'a> {
+        Progress {
+            interval: cli.progress_lines,
+            prefix: &cli.progress_prefix,
+            reported: 0,
+        }
+    }
+
+    /// Print progress lines for every interval reached until `processed` lines.
+    fn report(&mut self, processed: u64) {
+        let Some(k) = self.interval else {
+            return;
+        };
+        while self.reported + k <= processed {
+            self.reported += k;
+            println!("{}processed {} lines", self.prefix, self.reported);
+        }
+    }
+}
+
+type Stream = Box<dyn BufRead>;
+
+/// Selector of TARGET lines of [run], matching the regular expression index in parallel
+/// with the feature `parallel` when the options allow.
+enum Selector {
+    Serial(Select<Stream, Stream>),
+    #[cfg(feature = "parallel")]
+    Parallel(ParSelect<Stream, Stream>),
+}
+
+impl Selector {
+    fn new(config: &SelectConfig, target: Stream, index: Stream) -> Selector {
+        #[cfg(feature = "parallel")]
+        if ParSelect::<Stream, Stream>::supports(config) {
+            return Selector::Parallel(ParSelect::new(target, index, config.clone()));
+        }
+        Selector::Serial(config.run(target, index))
+    }
+
+    fn target_line(&self) -> u64 {
+        match self {
+            Selector::Serial(s) => s.target_line(),
+            #[cfg(feature = "parallel")]
+            Selector::Parallel(s) => s.target_line(),
+        }
+    }
+
+    fn target_line_offset(&self) -> u64 {
+        match self {
+            Selector::Serial(s) => s.target_line_offset(),
+            #[cfg(feature = "parallel")]
+            Selector::Parallel(s) => s.target_line_offset(),
+        }
+    }
+
+    fn index_line(&self) -> u64 {
+        match self {
+            Selector::Serial(s) => s.index_line(),
+            #[cfg(feature = "parallel")]
+            Selector::Parallel(s) => s.index_line(),
+        }
+    }
+
+    fn index_content(&self) -> &str {
+        match self {
+            Selector::Serial(s) => s.index_content(),
+            #[cfg(feature = "parallel")]
+            Selector::Parallel(s) => s.index_content(),
+        }
+    }
+
+    fn skip_rest(&mut self) -> Result<(), SelectError> {
+        match self {
+            Selector::Serial(s) => s.skip_rest(),
+            #[cfg(feature = "parallel")]
+            Selector::Parallel(s) => s.skip_rest(),
+        }
+    }
+}
+
+impl Iterator for Selector {
+    type Item = Result<String, SelectError>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        match self {
+            Selector::Serial(s) => s.next(),
+            #[cfg(feature = "parallel")]
+            Selector::Parallel(s) => s.next(),
+        }
+    }
+}
+
+/// Open TARGET stream for modes without INDEX.
+fn open_target(cli: &Cli) -> Result<Stream, RunError> {
+    match cli.files.as_slice() {
+        [f] => open_file(f),
+        [] => Ok(Box::new(io::stdin().lock())),
+        _ => Err(RunError(
+            ErrorKind::WrongNumberOfValues,
+            "files: only TARGET is accepted without INDEX".to_string(),
+        )),
+    }
+}
+
+/// Open pairs of TARGET and INDEX streams.
+///
+/// There is a pair for each TARGET file when multiple TARGET files are given without --continuous,
+/// otherwise only one pair.
+fn open_streams(cli: &Cli) -> Result<Vec<(Stream, Stream)>, RunError> {
+    if cli.target_regex.is_some() || cli.every.is_some() {
+        return Ok(vec![(open_target(cli)?, Box::new(io::empty()))]);
+    }
+    if !cli.lines.is_empty() {
+        for x in &cli.lines {
+            parse_range(cli, x)
+                .map_err(|e| RunError(ErrorKind::InvalidValue, format!("{}: {}", x, e)))?;
+        }
+        let index: String = cli
+            .lines
+            .iter()
+            .map(|x| format!("{}{}", x, cli.record_delimiter() as char))
+            .collect();
+        return Ok(vec![(open_target(cli)?, Box::new(Cursor::new(index)))]);
+    }
+    if let Some(n) = cli.head {
+        let index = format!(
+            "{}{}",
+            Range::Interval(1, n),
+            cli.record_delimiter() as char
+        );
+        return Ok(vec![(open_target(cli)?, Box::new(Cursor::new(index)))]);
+    }
+    if let Some(n) = cli.tail {
+        let (target, len) = read_target(cli)?;
+        let start = len.saturating_sub(n) + 1;
+        let index = format!(
+            "{}{}",
+            Range::Interval(start, u32::MAX),
+            cli.record_delimiter() as char
+        );
+        return Ok(vec![(
+            Box::new(Cursor::new(target)),
+            Box::new(Cursor::new(index)),
+        )]);
+    }
+    if let Some(pct) = cli.head_percent {
+        let (target, len) = read_target(cli)?;
+        let n = (u64::from(len) * u64::from(pct)).div_ceil(100) as u32;
+        let index = match n {
+            0 => String::new(),
+            n => format!(
+                "{}{}",
+                Range::Interval(1, n),
+                cli.record_delimiter() as char
+            ),
+        };
+        return Ok(vec![(
+            Box::new(Cursor::new(target)),
+            Box::new(Cursor::new(index)),
+        )]);
+    }
+
+    if let Some(m) = &cli.split_marker {
+        let (index, target) = split_at_marker(cli, m)?;
+        return Ok(vec![(
+            Box::new(Cursor::new(target)),
+            Box::new(Cursor::new(index)),
+        )]);
+    }
+
+    // None is stdin or --index-env
+    let (index, targets): (Option<&String>, Vec<&String>) = match cli.files.as_slice() {
+        _ if cli.index_env.is_some() => (None, cli.files.iter().collect()),
+        _ if !cli.index_file.is_empty() || cli.target_file.is_some() => {
+            (cli.index_file.first(), cli.target_file.iter().collect())
+        }
+        [f1, f2] if cli.swap_file_role => (Some(f2), vec![f1]),
+        [f1] if cli.swap_file_role || cli.stdin_is_index => (None, vec![f1]),
+        _ if cli.stdin_is_index => {
+            return Err(RunError(
+                ErrorKind::WrongNumberOfValues,
+                "files: only one file is accepted with --stdin-is-index".to_string(),
+            ))
+        }
+        [f1, targets @ ..] if !cli.swap_file_role => (Some(f1), targets.iter().collect()),
+        _ => {
+            return Err(RunError(
+                ErrorKind::WrongNumberOfValues,
+                "files".to_string(),
+            ))
+        }
+    };
+
+    let mut index = match index {
+        _ if cli.index_file.len() > 1 => open_index_files(cli)?,
+        Some(f) if Path::new(f).is_dir() => open_index_dir(cli, f)?,
+        Some(f) => open_file(f)?,
+        None => match &cli.index_env {
+            Some(var) => env_index(var)?,
+            None => Box::new(io::stdin().lock()),
+        },
+    };
+    if cli.json_index {
+        index = json_index(cli, index)?;
+    }
+    if !cli.intersect_index.is_empty() {
+        index = intersect_index_files(cli, index)?;
+    }
+    match targets.as_slice() {
+        [] => Ok(vec![(Box::new(io::stdin().lock()), index)]),
+        [f] => Ok(vec![(open_file(f)?, index)]),
+        _ if cli.continuous => {
+            let mut target: Box<dyn Read> = Box::new(io::empty());
+            for f in targets {
+                check_file(f)?;
+                let file = File::open(f).map_err(|x| file_error(f, x))?;
+                target = Box::new(target.chain(file));
+            }
+            Ok(vec![(Box::new(BufReader::new(target)), index)])
+        }
+        _ => {
+            let mut content = Vec::new();
+            index
+                .read_to_end(&mut content)
+                .map_err(|x| RunError(ErrorKind::Io, x.to_string()))?;
+            targets
+                .into_iter()
+                .map(|f| {
+                    Ok((
+                        open_file(f)?,
+                        Box::new(Cursor::new(content.clone())) as Stream,
+                    ))
+                })
+                .collect()
+        }
+    }
+}
+
+/// Read INDEX from the environment variable, \n in the value is a line break.
+fn env_index(var: &str) -> Result<Stream, RunError> {
+    match env::var(var) {
+        Ok(x) if x.is_empty() => Err(RunError(
+            ErrorKind::InvalidValue,
+            format!("{}: environment variable is empty", var),
+        )),
+        Ok(x) => Ok(Box::new(Cursor::new(x.replace("\\n", "\n")))),
+        Err(env::VarError::NotPresent) => Err(RunError(
+            ErrorKind::InvalidValue,
+            format!("{}: environment variable is not set", var),
+        )),
+        Err(x) => Err(RunError(ErrorKind::InvalidValue, format!("{}: {}", var, x))),
+    }
+}
+
+/// Read the file and split it into INDEX and TARGET at the first line equal to the marker.
+fn split_at_marker(cli: &Cli, marker: &str) -> Result<(String, String), RunError> {
+    let (name, mut input) = match cli.files.as_slice() {
+        [f] => (f.as_str(), open_file(f)?),
+        [] => ("stdin", Box::new(io::stdin().lock()) as Stream),
+        _ => {
+            return Err(RunError(
+                ErrorKind::WrongNumberOfValues,
+                "files: only one file is accepted with --split-marker".to_string(),
+            ))
+        }
+    };
+    let mut content = String::new();
+    input
+        .read_to_string(&mut content)
+        .map_err(|x| RunError(ErrorKind::Io, format!("{}: {}", name, x)))?;
+    let delimiter = cli.record_delimiter() as char;
+    let mut offset = 0;
+    for line in content.split_inclusive(delimiter) {
+        let mut record = line.to_string();
+        rstrip_delimiter(&mut record, cli.record_delimiter());
+        if record == marker {
+            let target = content[offset + line.len()..].to_string();
+            content.truncate(offset);
+            return Ok((content, target));
+        }
+        offset += line.len();
+    }
+    Err(RunError(
+        ErrorKind::InvalidValue,
+        format!("{}: marker {} not found", name, marker),
+    ))
+}
+
+/// Read the whole TARGET into memory and count its lines.
+fn read_target(cli: &Cli) -> Result<(Vec<u8>, u32), RunError> {
+    let mut target = Vec::new();
+    open_target(cli)?
+        .read_to_end(&mut target)
+        .map_err(|x| RunError(ErrorKind::Io, x.to_string()))?;
+    let delimiters = target
+        .iter()
+        .filter(|&&b| b == cli.record_delimiter())
+        .count();
+    let len = match target.last() {
+        Some(&b) if b != cli.record_delimiter() => delimiters + 1,
+        _ => delimiters,
+    } as u32;
+    Ok((target, len))
+}
+
+/// Parse a line of the line number index.
+fn parse_range<'a>(cli: &Cli, line: &'a str) -> nom::IResult<&'a str, Range> {
+    if cli.zero_based {
+        range_zero_based(line)
+    } else {
+        range(line)
+    }
+}
+
+/// Read regular files in the directory as fragments of a line number index
+/// and build the index of their union.
+///
+/// Files are read in order of their names, subdirectories and hidden files are skipped.
+fn open_index_dir(cli: &Cli, dir: &str) -> Result<Stream, RunError> {
+    if !cli.index_line_number {
+        return Err(RunError(
+            ErrorKind::ArgumentConflict,
+            format!("{}: directory INDEX requires --index-line-number", dir),
+        ));
+    }
+
+    let mut paths: Vec<PathBuf> = fs::read_dir(dir)
+        .and_then(|entries| {
+            entries
+                .map(|x| x.map(|e| e.path()))
+                .collect::<Result<_, _>>()
+        })
+        .map_err(|x| RunError(ErrorKind::Io, format!("{}: {}", dir, x)))?;
+    paths.retain(|p| {
+        p.is_file()
+            && !p
+                .file_name()
+                .is_some_and(|x| x.to_string_lossy().starts_with('.'))
+    });
+    paths.sort();
+    merge_index_files(cli, &paths)
+}
+
+/// Build the index of the union of the line number indexes of the --index-file files.
+fn open_index_files(cli: &Cli) -> Result<Stream, RunError> {
+    if !cli.index_line_number {
+        return Err(RunError(
+            ErrorKind::ArgumentConflict,
+            "multiple --index-file require --index-line-number".to_string(),
+        ));
+    }
+    let paths: Vec<PathBuf> = cli.index_file.iter().map(PathBuf::from).collect();
+    merge_index_files(cli, &paths)
+}
+
+/// Read the files as line number indexes and build the index of their union.
+fn merge_index_files(cli: &Cli, paths: &[PathBuf]) -> Result<Stream, RunError> {
+    if cli.index_base != 0 {
+        return Err(RunError(
+            ErrorKind::ArgumentConflict,
+            "the union of line number indexes cannot be used with --index-base".to_string(),
+        ));
+    }
+    let mut ranges: Vec<Range> = Vec::new();
+    for p in paths {
+        let content = fs::read_to_string(p)
+            .map_err(|x| RunError(ErrorKind::Io, format!("{}: {}", p.display(), x)))?;
+        ranges.extend(read_index_ranges(cli, &p.display().to_string(), &content)?);
+    }
+    Ok(ranges_index(cli, ranges))
+}
+
+/// Convert the JSON array of line numbers in INDEX into the line number index.
+fn json_index(cli: &Cli, mut index: Stream) -> Result<Stream, RunError> {
+    let mut content = String::new();
+    index
+        .read_to_string(&mut content)
+        .map_err(|x| RunError(ErrorKind::Io, format!("INDEX: {}", x)))?;
+    let values: Vec<serde_json::Value> = serde_json::from_str(&content)
+        .map_err(|x| RunError(ErrorKind::InvalidValue, format!("INDEX: {}", x)))?;
+    let ranges = values
+        .iter()
+        .map(|v| match v.as_u64() {
+            Some(n @ 1..=0xffff_ffff) => Ok(Range::Single(n as u32)),
+            _ => Err(RunError(
+                ErrorKind::InvalidValue,
+                format!("INDEX: {} is not a positive integer", v),
+            )),
+        })
+        .collect::<Result<_, _>>()?;
+    Ok(ranges_index(cli, ranges))
+}
+
+/// Build the index of the intersection of INDEX and the line number indexes of the --intersect-index files.
+fn intersect_index_files(cli: &Cli, mut index: Stream) -> Result<Stream, RunError> {
+    let mut content = String::new();
+    index
+        .read_to_string(&mut content)
+        .map_err(|x| RunError(ErrorKind::Io, format!("INDEX: {}", x)))?;
+    let mut ranges = read_index_ranges(cli, "INDEX", &content)?;
+    for f in &cli.intersect_index {
+        let content =
+            fs::read_to_string(f).map_err(|x| RunError(ErrorKind::Io, format!("{}: {}", f, x)))?;
+        ranges = intersect_ranges(ranges, read_index_ranges(cli, f, &content)?);
+    }
+    Ok(ranges_index(cli, ranges))
+}
+
+/// Parse the content of a line number index, empty lines and comment lines are skipped.
+fn read_index_ranges(cli: &Cli, name: &str, content: &str) -> Result<Vec<Range>, RunError> {
+    let mut ranges: Vec<Range> = Vec::new();
+    // end of the previous range, the base of relative ranges
+    let mut base = 0;
+    for (i, line) in content.lines().enumerate() {
+        let line = line.trim_end_matches('\r');
+        if line.is_empty() || line.trim_start().starts_with('#') {
+            continue;
+        }
+        let (_, r) = parse_range(cli, line).map_err(|x| {
+            RunError(
+                ErrorKind::InvalidValue,
+                format!("{}:{}: {}: {}", name, i + 1, line, x),
+            )
+        })?;
+        let t = Type::Number(r.resolve(base));
+        if t.end() != u32::MAX {
+            base = t.end();
+        }
+        if cli.strict_range && t.start() > t.end() {
+            return Err(RunError(
+                ErrorKind::InvalidValue,
+                format!("{}:{}: {}: start is greater than end", name, i + 1, line),
+            ));
+        }
+        ranges.push(Range::Interval(t.start().max(1), t.end()));
+    }
+    Ok(ranges)
+}
+
+/// Line number index of the minimal intervals covering the ranges.
+fn ranges_index(cli: &Cli, ranges: Vec<Range>) -> Stream {
+    let index: String = merge_ranges(ranges)
+        .into_iter()
+        .map(Type::Number)
+        .map(|t| (t.start(), t.end()))
+        // back to the numbering the index is read with
+        .map(|(s, e)| match cli.zero_based {
+            true if e == u32::MAX => (s - 1, e),
+            true => (s - 1, e - 1),
+            false => (s, e),
+        })
+        .map(|(s, e)| {
+            format!(
+                "{}{}",
+                Range::Interval(s, e),
+                cli.record_delimiter() as char
+            )
+        })
+        .collect();
+    Box::new(Cursor::new(index))
+}
+
+fn open_file(name: &str) -> Result<Stream, RunError> {
+    check_file(name)?;
+    File::open(name)
+        .map(|x| Box::new(BufReader::new(x)) as Stream)
+        .map_err(|x| file_error(name, x))
+}
+
+/// Check that the file exists and is not a directory before opening it.
+fn check_file(name: &str) -> Result<(), RunError> {
+    match fs::metadata(name) {
+        Ok(m) if m.is_dir() => Err(RunError(
+            ErrorKind::InvalidValue,
+            format!("{}: is a directory", name),
+        )),
+        Ok(_) => Ok(()),
+        Err(x) => Err(file_error(name, x)),
+    }
+}
+
+/// Error of opening the file, with a plain message for the common causes.
+fn file_error(name: &str, x: io::Error) -> RunError {
+    let message = match x.kind() {
+        io::ErrorKind::NotFound => "no such file".to_string(),
+        io::ErrorKind::PermissionDenied => "permission denied".to_string(),
+        _ => x.to_string(),
+    };
+    RunError(ErrorKind::InvalidValue, format!("{}: {}", name, message))
+}
+
+/// Compile the trimmed first line of the file as a regular expression.
+fn read_regex_file(name: &str) -> Result<pattern::Regex, RunError> {
+    let content = fs::read_to_string(name)
+        .map_err(|x| RunError(ErrorKind::Io, format!("{}: {}", name, x)))?;
+    let pattern = content.lines().next().unwrap_or_default().trim();
+    pattern::Regex::new(pattern)
+        .map_err(|x| RunError(ErrorKind::InvalidValue, format!("{}: {}", name, x)))
+}
+
+/// Anchor the regular expressions to match the whole line when `line_regexp` is set.
+///
+/// Already anchored patterns are wrapped as well, the anchors are idempotent.
+fn line_regexps(
+    r: Vec<pattern::Regex>,
+    line_regexp: bool,
+) -> Result<Vec<pattern::Regex>, RunError> {
+    if !line_regexp {
+        return Ok(r);
+    }
+    r.iter()
+        .map(|x| {
+            pattern::Regex::new(&format!("^(?:{})$", x.as_str()))
+                .map_err(|x| RunError(ErrorKind::InvalidValue, x.to_string()))
+        })
+        .collect()
+}
+
+fn new_index_type(
+    mut r: Vec<pattern::Regex>,
+    index_line_number: bool,
+) -> Result<Option<Type>, RunError> {
+    if index_line_number {
+        return Ok(None);
+    }
+    if r.len() > 1 {
+        return Ok(Some(Type::ReAny(r)));
+    }
+    let r = match r.pop() {
+        Some(x) => x,
+        None => pattern::Regex::new(".+")
+            .map_err(|x| RunError(ErrorKind::InvalidValue, x.to_string()))?,
+    };
+    Ok(Some(Type::Re(r)))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::fs::{self, File};
+    use std::io::Write;
+    use std::process::{Command, Stdio};
+    use tempfile::TempDir;
+
+    macro_rules! test_new_index_type {
+        ($name:ident, $regex:expr, $index_line_number:expr, $want:expr) => {
+            #[test]
+            fn $name() {
+                let r: Vec<pattern::Regex> = $regex
+                    .iter()
+                    .map(|x| pattern::Regex::new(x).unwrap())
+                    .collect();
+                let got = match new_index_type(r, $index_line_number).unwrap() {
+                    None => "None".to_string(),
+                    Some(Type::Re(r)) => format!("Re({})", r),
+                    Some(Type::ReAny(rs)) => format!("ReAny({:?})", rs),
+                    Some(x) => panic!("unexpected index type {:?}", x),
+                };
+                assert_eq!($want, got);
+            }
+        };
+    }
+
+    test_new_index_type!(new_index_type_default, Vec::<&str>::new(), false, "Re(.+)");
+    test_new_index_type!(new_index_type_regex, ["^a"], false, "Re(^a)");
+    test_new_index_type!(
+        new_index_type_regexes,
+        ["^a", "b"],
+        false,
+        "ReAny([Regex(\"^a\"), Regex(\"b\")])"
+    );
+    test_new_index_type!(new_index_type_line_number, ["^a"], true, "None");
+
+    macro_rules! test_e2e {
+        ($name:expr, $dir:expr, $bin:expr, $args:expr, $data:expr, $stdin:expr, $want:expr) => {{
+            eprint!("test {} ... ", $name);
+
+            let f1_path = $dir.path().join(format!("{}_f1", $name));
+            {
+                let mut f1 = File::create(&f1_path).expect("failed to create 1st file");
+                f1.write_all($data.as_bytes())
+                    .expect("failed to write data to 1st file");
+            }
+
+            let mut args = vec![f1_path.to_str().unwrap()];
+            args.extend_from_slice(&$args);
+            let mut process = Command::new($bin)
+                .args(args.clone())
+                .stdin(Stdio::piped())
+                .stdout(Stdio::piped())
+                .stderr(Stdio::piped())
+                .spawn()
+                .expect("failed to spawn process");
+            if let Some(ref mut stdin) = process.stdin {
+                stdin
+                    .write_all($stdin.as_bytes())
+                    .expect("failed to write data to stdin");
+            }
+
+            let output = process.wait_with_output().expect("failed to wait process");
+            assert!(output.status.success());
+
+            let got = String::from_utf8(output.stdout).expect("failed to read stdout");
+            let err = String::from_utf8(output.stderr).expect("failed to read stderr");
+
+            assert_eq!(
+                $want, got,
+                "{} stdout, args: {:?} err: {}",
+                $name, &args, err
+            );
+
+            eprintln!("ok");
+        }};
+    }
+
+    macro_rules! test_e2e_files {
+        ($name:expr, $dir:expr, $bin:expr, $args:expr, $index:expr, $target:expr, $want:expr) => {{
+            eprint!("test {} ... ", $name);
+
+            let f1_path = $dir.path().join(format!("{}_f1", $name));
+            let f2_path = $dir.path().join(format!("{}_f2", $name));
+            {
+                let mut f1 = File::create(&f1_path).expect("failed to create 1st file");
+                let mut f2 = File::create(&f2_path).expect("failed to create 2nd file");
+                f1.write_all($index.as_bytes())
+                    .expect("failed to write index to 1st file");
+                f2.write_all($target.as_bytes())
+                    .expect("failed to write target to 2nd file");
+            }
+
+            let mut args = vec![f1_path.to_str().unwrap(), f2_path.to_str().unwrap()];
+            args.extend_from_slice(&$args);
+            let output = Command::new($bin)
+                .args(args.clone())
+                .output()
+                .expect("failed to run process");
+            assert!(
+                output.status.success(),
+                "{} status, args: {:?}",
+                $name,
+                &args
+            );
+            let got = String::from_utf8(output.stdout).expect("failed to read stdout");
+            let err = String::from_utf8(output.stderr).expect("failed to read stderr");
+            assert_eq!(
+                $want, got,
+                "{} stdout, args: {:?} err: {}",
+                $name, &args, err
+            );
+
+            eprintln!("ok");
+        }};
+    }
+
+    macro_rules! e2e_files_output {
+        ($name:expr, $dir:expr, $bin:expr, $args:expr, $index:expr, $target:expr) => {{
+            let f1_path = $dir.path().join(format!("{}_f1", $name));
+            let f2_path = $dir.path().join(format!("{}_f2", $name));
+            {
+                let mut f1 = File::create(&f1_path).expect("failed to create 1st file");
+                let mut f2 = File::create(&f2_path).expect("failed to create 2nd file");
+                f1.write_all($index.as_bytes())
+                    .expect("failed to write index to 1st file");
+                f2.write_all($target.as_bytes())
+                    .expect("failed to write target to 2nd file");
+            }
+
+            let mut args = vec![f1_path.to_str().unwrap(), f2_path.to_str().unwrap()];
+            args.extend_from_slice(&$args);
+            Command::new($bin)
+                .args(args)
+                .output()
+                .expect("failed to run process")
+        }};
+    }
+
+    macro_rules! test_e2e_files_fail {
+        ($name:expr, $dir:expr, $bin:expr, $args:expr, $index:expr, $target:expr, $want:expr, $want_err:expr) => {{
+            eprint!("test {} ... ", $name);
+
+            let f1_path = $dir.path().join(format!("{}_f1", $name));
+            let f2_path = $dir.path().join(format!("{}_f2", $name));
+            {
+                let mut f1 = File::create(&f1_path).expect("failed to create 1st file");
+                let mut f2 = File::create(&f2_path).expect("failed to create 2nd file");
+                f1.write_all($index.as_bytes())
+                    .expect("failed to write index to 1st file");
+                f2.write_all($target.as_bytes())
+                    .expect("failed to write target to 2nd file");
+            }
+
+            let mut args = vec![f1_path.to_str().unwrap(), f2_path.to_str().unwrap()];
+            args.extend_from_slice(&$args);
+            let output = Command::new($bin)
+                .args(args.clone())
+                .output()
+                .expect("failed to run process");
+            assert!(
+                !output.status.success(),
+                "{} status, args: {:?}",
+                $name,
+                &args
+            );
+            let got = String::from_utf8(output.stdout).expect("failed to read stdout");
+            let err = String::from_utf8(output.stderr).expect("failed to read stderr");
+            assert_eq!($want, got, "{} stdout, args: {:?}", $name, &args);
+            assert!(
+                err.contains($want_err),
+                "{} stderr, args: {:?} err: {}",
+                $name,
+                &args,
+                err
+            );
+
+            eprintln!("ok");
+        }};
+    }
+
+    #[test]
+    fn main() {
+        let status = Command::new("cargo")
+            .arg("build")
+            .status()
+            .expect("failed to execute build");
+        assert!(status.success(), "{}", "cargo build");
+
+        let bin = "./target/debug/lisel";
+        let output = Command::new(bin)
             .arg("--help")
             .output()
             .expect("failed to execute help");
         assert!(output.status.success(), "{}", "help status");
-        assert!(output.stdout.len() > 0, "{}", "help stdout");
+        assert!(!output.stdout.is_empty(), "{}", "help stdout");
+
+        let output = Command::new(bin)
+            .args(["--generate-completions", "bash"])
+            .output()
+            .expect("failed to execute generate completions");
+        assert!(output.status.success(), "{}", "completions status");
+        let got = String::from_utf8(output.stdout).expect("failed to read stdout");
+        assert!(got.contains("lisel"), "{}", "completions stdout");
+
+        let output = Command::new(bin)
+            .arg("--man")
+            .output()
+            .expect("failed to execute man");
+        assert!(output.status.success(), "{}", "man status");
+        let got = String::from_utf8(output.stdout).expect("failed to read stdout");
+        assert!(
+            got.lines().any(|l| l.starts_with(".TH ")),
+            "{}",
+            "man stdout"
+        );
+
+        let tmp_dir = TempDir::new_in(".").unwrap();
+
+        test_e2e!(
+            "e2e_re_default",
+            tmp_dir,
+            bin,
+            [],
+            "1\n\n1\n",
+            "l1\nl2\nl3\nl4\nl5\n",
+            "l1\nl3\n"
+        );
+        test_e2e!(
+            "e2e_re_default_invert",
+            tmp_dir,
+            bin,
+            ["--index-invert-match"],
+            "1\n\n1\n",
+            "l1\nl2\nl3\nl4\nl5\n",
+            "l2\nl4\nl5\n"
+        );
+        test_e2e!(
+            "e2e_re_default_swap",
+            tmp_dir,
+            bin,
+            ["--swap-file-role"],
+            "l1\nl2\nl3\nl4\nl5\n",
+            "1\n\n1\n",
+            "l1\nl3\n"
+        );
+        test_e2e_files!(
+            "e2e_files_capture_line",
+            tmp_dir,
+            bin,
+            ["--index-regex", r"^goto (\d+)$", "--capture-line", "1"],
+            "goto 4\nstay\ngoto 2\ngoto 9\ngoto 4\n",
+            "l1\nl2\nl3\nl4\nl5\n",
+            "l4\nl2\nl4\n"
+        );
+        test_e2e_files!(
+            "e2e_files_capture_line_name",
+            tmp_dir,
+            bin,
+            [
+                "--index-regex",
+                r"line=(?<n>\d+)",
+                "--capture-line",
+                "n",
+                "--json"
+            ],
+            "t=1 line=3\nt=2\nt=3 line=1\n",
+            "l1\nl2\nl3\n",
+            "{\"line\":3,\"text\":\"l3\"}\n{\"line\":1,\"text\":\"l1\"}\n"
+        );
+        for (name, group) in [
+            ("e2e_files_capture_line_no_group", "2"),
+            ("e2e_files_capture_line_no_group_name", "n"),
+        ] {
+            test_e2e_files_fail!(
+                name,
+                tmp_dir,
+                bin,
+                ["--index-regex", r"^goto (\d+)$", "--capture-line", group],
+                "goto 1\n",
+                "l1\n",
+                "",
+                "no capture group"
+            );
+        }
+        for (name, flag, want_out, want_err) in [
+            ("e2e_files_capture_line_quiet", "--quiet", "", ""),
+            (
+                "e2e_files_capture_line_stats",
+                "--stats",
+                "l4\nl2\n",
+                "target=5 index=3 emitted=2\n",
+            ),
+        ] {
+            eprint!("test {} ... ", name);
+
+            let output = e2e_files_output!(
+                name,
+                tmp_dir,
+                bin,
+                [
+                    "--index-regex",
+                    r"^goto (\d+)$",
+                    "--capture-line",
+                    "1",
+                    flag
+                ],
+                "goto 4\nstay\ngoto 2\n",
+                "l1\nl2\nl3\nl4\nl5\n"
+            );
+            assert!(output.status.success(), "{} status", name);
+            let got = String::from_utf8(output.stdout).expect("failed to read stdout");
+            let err = String::from_utf8(output.stderr).expect("failed to read stderr");
+            assert_eq!(want_out, got, "{} stdout", name);
+            assert_eq!(want_err, err, "{} stderr", name);
+
+            eprintln!("ok");
+        }
+        test_e2e_files_fail!(
+            "e2e_files_capture_line_no_regex",
+            tmp_dir,
+            bin,
+            ["--capture-line", "1"],
+            "1\n",
+            "l1\n",
+            "",
+            "--capture-line requires"
+        );
+        test_e2e!(
+            "e2e_json_index_stdin",
+            tmp_dir,
+            bin,
+            ["--json-index", "--stdin-is-index"],
+            "l1\nl2\nl3\nl4\nl5\nl6\n",
+            "[1,3,5]",
+            "l1\nl3\nl5\n"
+        );
+        test_e2e_files!(
+            "e2e_files_json_index_unordered",
+            tmp_dir,
+            bin,
+            ["--json-index"],
+            " [ 5, 1,\n3, 1 ]\n",
+            "l1\nl2\nl3\nl4\nl5\nl6\n",
+            "l1\nl3\nl5\n"
+        );
+        for (name, index) in [
+            ("e2e_files_json_index_zero", "[1,0]"),
+            ("e2e_files_json_index_negative", "[-1]"),
+            ("e2e_files_json_index_string", "[\"1\"]"),
+        ] {
+            test_e2e_files_fail!(
+                name,
+                tmp_dir,
+                bin,
+                ["--json-index"],
+                index,
+                "l1\n",
+                "",
+                "is not a positive integer"
+            );
+        }
+        test_e2e_files_fail!(
+            "e2e_files_json_index_not_array",
+            tmp_dir,
+            bin,
+            ["--json-index"],
+            "1\n2\n",
+            "l1\n",
+            "",
+            "INDEX"
+        );
+        test_e2e!(
+            "e2e_re_stdin_is_index",
+            tmp_dir,
+            bin,
+            ["--stdin-is-index"],
+            "l1\nl2\nl3\nl4\nl5\n",
+            "1\n\n1\n",
+            "l1\nl3\n"
+        );
+        test_e2e!(
+            "e2e_number_stdin_is_index",
+            tmp_dir,
+            bin,
+            ["--stdin-is-index", "--index-line-number"],
+            "l1\nl2\nl3\nl4\nl5\n",
+            "2\n4,\n",
+            "l2\nl4\nl5\n"
+        );
+        test_e2e!(
+            "e2e_number_stdin_is_target",
+            tmp_dir,
+            bin,
+            ["--index-line-number"],
+            "2\n4,\n",
+            "l1\nl2\nl3\nl4\nl5\n",
+            "l2\nl4\nl5\n"
+        );
+        test_e2e_files_fail!(
+            "e2e_files_stdin_is_index_two_files",
+            tmp_dir,
+            bin,
+            ["--stdin-is-index"],
+            "1\n",
+            "l1\n",
+            "",
+            "--stdin-is-index"
+        );
+
+        {
+            let index_path = tmp_dir.path().join("e2e_target_not_file_index");
+            fs::write(&index_path, "1\n").expect("failed to write index");
+            let dir_path = tmp_dir.path().join("e2e_target_not_file_dir");
+            fs::create_dir_all(&dir_path).expect("failed to create dir");
+            let missing_path = tmp_dir.path().join("e2e_target_not_file_missing");
+            for (name, target, want_err) in [
+                ("e2e_target_directory", &dir_path, "is a directory"),
+                ("e2e_target_not_found", &missing_path, "no such file"),
+            ] {
+                eprint!("test {} ... ", name);
+
+                let args = vec![index_path.to_str().unwrap(), target.to_str().unwrap()];
+                let output = Command::new(bin)
+                    .args(args.clone())
+                    .output()
+                    .expect("failed to run process");
+                assert_eq!(Some(2), output.status.code(), "{} status", name);
+                let err = String::from_utf8(output.stderr).expect("failed to read stderr");
+                let want_err = format!("{}: {}", target.to_str().unwrap(), want_err);
+                assert!(
+                    err.contains(&want_err),
+                    "{} stderr, args: {:?} err: {}",
+                    name,
+                    &args,
+                    err
+                );
+
+                eprintln!("ok");
+            }
+        }
+
+        test_e2e_files!(
+            "e2e_files_re_default",
+            tmp_dir,
+            bin,
+            [],
+            "1\n\n1\n",
+            "l1\nl2\nl3\nl4\nl5\n",
+            "l1\nl3\n"
+        );
+        test_e2e_files!(
+            "e2e_files_re",
+            tmp_dir,
+            bin,
+            ["--index-regex", "^$"],
+            "1\n\n1\n",
+            "l1\nl2\nl3\nl4\nl5\n",
+            "l2\n"
+        );
+        test_e2e_files!(
+            "e2e_files_re_invert",
+            tmp_dir,
+            bin,
+            ["--index-regex", "^$", "--index-invert-match"],
+            "1\n\n1\n",
+            "l1\nl2\nl3\nl4\nl5\n",
+            "l1\nl3\nl4\nl5\n"
+        );
+        test_e2e_files!(
+            "e2e_files_re_default_swap",
+            tmp_dir,
+            bin,
+            ["--swap-file-role"],
+            "l1\nl2\nl3\nl4\nl5\n",
+            "1\n\n1\n",
+            "l1\nl3\n"
+        );
+        test_e2e_files!(
+            "e2e_files_number",
+            tmp_dir,
+            bin,
+            ["--index-line-number"],
+            "1\n3,4\n",
+            "l1\nl2\nl3\nl4\nl5\n",
+            "l1\nl3\nl4\n"
+        );
+        test_e2e_files!(
+            "e2e_files_number",
+            tmp_dir,
+            bin,
+            ["--index-line-number", "--index-invert-match"],
+            "1\n3,4\n",
+            "l1\nl2\nl3\nl4\nl5\n",
+            "l2\nl5\n"
+        );
+
+        test_e2e_files!(
+            "e2e_files_number_group_ordinal",
+            tmp_dir,
+            bin,
+            ["--index-line-number", "--group-ordinal"],
+            "1,2\n4\n6,8\n",
+            "l1\nl2\nl3\nl4\nl5\nl6\nl7\nl8\n",
+            "1:l1\n2:l2\n1:l4\n1:l6\n2:l7\n3:l8\n"
+        );
+        test_e2e_files!(
+            "e2e_files_re_group_ordinal",
+            tmp_dir,
+            bin,
+            ["--group-ordinal"],
+            "1\n1\n\n1\n",
+            "l1\nl2\nl3\nl4\n",
+            "1:l1\n2:l2\n1:l4\n"
+        );
+        test_e2e_files!(
+            "e2e_files_number_output_number",
+            tmp_dir,
+            bin,
+            ["--index-line-number", "--output-number"],
+            "2\n5\n7,8\n",
+            "l1\nl2\nl3\nl4\nl5\nl6\nl7\nl8\n",
+            "1:l2\n2:l5\n3:l7\n4:l8\n"
+        );
+        test_e2e_files!(
+            "e2e_files_number_output_number_group_ordinal",
+            tmp_dir,
+            bin,
+            ["--index-line-number", "--output-number", "--group-ordinal"],
+            "2\n5\n7,8\n",
+            "l1\nl2\nl3\nl4\nl5\nl6\nl7\nl8\n",
+            "1:1:l2\n2:1:l5\n3:1:l7\n4:2:l8\n"
+        );
+        test_e2e_files!(
+            "e2e_files_number_output_number_separator",
+            tmp_dir,
+            bin,
+            [
+                "--index-line-number",
+                "--output-number",
+                "--output-separator",
+                " "
+            ],
+            "2\n4\n",
+            "l1\nl2\nl3\nl4\n",
+            "1:l2 2:l4\n"
+        );
+        test_e2e_files!(
+            "e2e_files_number_output_number_unique",
+            tmp_dir,
+            bin,
+            [
+                "--index-line-number",
+                "--output-order=index",
+                "--output-number",
+                "--unique"
+            ],
+            "1,2\n2,3\n",
+            "a\nb\nc\n",
+            "1:a\n2:b\n3:c\n"
+        );
+
+        test_e2e_files!(
+            "e2e_files_number_end_marker",
+            tmp_dir,
+            bin,
+            ["--index-line-number", "--end-marker", "__END__"],
+            "1\n3\n",
+            "l1\nl2\nl3\nl4\n",
+            "l1\nl3\n__END__\n"
+        );
+        {
+            let name = "e2e_files_number_end_marker_empty";
+            eprint!("test {} ... ", name);
+
+            let output = e2e_files_output!(
+                name,
+                tmp_dir,
+                bin,
+                ["--index-line-number", "--end-marker", "__END__"],
+                "5\n",
+                "l1\nl2\n"
+            );
+            // nothing is selected
+            assert_eq!(Some(1), output.status.code(), "{} status", name);
+            assert_eq!(b"__END__\n".to_vec(), output.stdout, "{} stdout", name);
+
+            eprintln!("ok");
+        }
+        test_e2e_files_fail!(
+            "e2e_files_number_end_marker_error",
+            tmp_dir,
+            bin,
+            ["--index-line-number", "--end-marker", "__END__"],
+            "1\nx\n",
+            "l1\nl2\n",
+            "l1\n",
+            "Parse"
+        );
+
+        {
+            let name = "e2e_number_index_dir";
+            eprint!("test {} ... ", name);
+
+            let index_dir = tmp_dir.path().join(format!("{}_index", name));
+            let target_path = tmp_dir.path().join(format!("{}_target", name));
+            fs::create_dir_all(index_dir.join("sub")).expect("failed to create index dir");
+            for (f, data) in [
+                ("b", "2,3\n"),
+                ("a", "# a\n1\n5,6\n"),
+                (".hidden", "4\n"),
+                ("sub/c", "4\n"),
+            ] {
+                fs::write(index_dir.join(f), data).expect("failed to write index fragment");
+            }
+            fs::write(&target_path, "l1\nl2\nl3\nl4\nl5\nl6\nl7\n")
+                .expect("failed to write target");
+
+            let args = vec![
+                "--index-line-number",
+                index_dir.to_str().unwrap(),
+                target_path.to_str().unwrap(),
+            ];
+            let output = Command::new(bin)
+                .args(args.clone())
+                .output()
+                .expect("failed to run process");
+            assert!(
+                output.status.success(),
+                "{} status, args: {:?}",
+                name,
+                &args
+            );
+            let got = String::from_utf8(output.stdout).expect("failed to read stdout");
+            assert_eq!(
+                "l1\nl2\nl3\nl5\nl6\n", got,
+                "{} stdout, args: {:?}",
+                name, &args
+            );
+
+            eprintln!("ok");
+        }
+
+        {
+            let name = "e2e_number_index_relative";
+            eprint!("test {} ... ", name);
+
+            let index_dir = tmp_dir.path().join(format!("{}_index", name));
+            let index_path = tmp_dir.path().join(format!("{}_index_file", name));
+            let target_path = tmp_dir.path().join(format!("{}_target", name));
+            fs::create_dir_all(&index_dir).expect("failed to create index dir");
+            fs::write(index_dir.join("a"), "2\n+2\n").expect("failed to write index fragment");
+            fs::write(index_dir.join("b"), "6\n").expect("failed to write index fragment");
+            fs::write(&index_path, "2\n+2\n").expect("failed to write index");
+            fs::write(&target_path, "l1\nl2\nl3\nl4\nl5\nl6\nl7\n")
+                .expect("failed to write target");
+            let (dir, file, target) = (
+                index_dir.to_str().unwrap(),
+                index_path.to_str().unwrap(),
+                target_path.to_str().unwrap(),
+            );
+            let b_path = index_dir.join("b");
+            let b = b_path.to_str().unwrap();
+            for (args, want) in [
+                (vec!["-n", dir, target], "l2\nl4\nl6\n"),
+                (
+                    vec![
+                        "-n",
+                        "--index-file",
+                        file,
+                        "--index-file",
+                        b,
+                        "--target-file",
+                        target,
+                    ],
+                    "l2\nl4\nl6\n",
+                ),
+                (vec!["-n", "--intersect-index", file, b, target], ""),
+                (
+                    vec!["-n", "--intersect-index", file, dir, target],
+                    "l2\nl4\n",
+                ),
+            ] {
+                let output = Command::new(bin)
+                    .args(args.clone())
+                    .output()
+                    .expect("failed to run process");
+                let got = String::from_utf8(output.stdout).expect("failed to read stdout");
+                assert_eq!(want, got, "{} stdout, args: {:?}", name, &args);
+                assert_eq!(
+                    !want.is_empty(),
+                    output.status.success(),
+                    "{} status, args: {:?}",
+                    name,
+                    &args
+                );
+            }
+
+            eprintln!("ok");
+        }
+
+        {
+            let name = "e2e_number_index_base_conflicts";
+            eprint!("test {} ... ", name);
+
+            let index_dir = tmp_dir.path().join(format!("{}_index", name));
+            let index_path = tmp_dir.path().join(format!("{}_index_file", name));
+            let target_path = tmp_dir.path().join(format!("{}_target", name));
+            fs::create_dir_all(&index_dir).expect("failed to create index dir");
+            fs::write(index_dir.join("a"), "1\n").expect("failed to write index fragment");
+            fs::write(&index_path, "2\n").expect("failed to write index");
+            fs::write(&target_path, "l1\nl2\nl3\n").expect("failed to write target");
+            let (dir, file, target) = (
+                index_dir.to_str().unwrap(),
+                index_path.to_str().unwrap(),
+                target_path.to_str().unwrap(),
+            );
+            for args in [
+                vec!["-n", dir, target],
+                vec![
+                    "-n",
+                    "--index-file",
+                    file,
+                    "--index-file",
+                    file,
+                    "--target-file",
+                    target,
+                ],
+                vec!["-n", "--intersect-index", file, file, target],
+                vec!["--json-index", file, target],
+                vec!["-n", "--explain", file],
+            ] {
+                let mut args = args;
+                args.extend(["--index-base", "1"]);
+                let output = Command::new(bin)
+                    .args(args.clone())
+                    .output()
+                    .expect("failed to run process");
+                let err = String::from_utf8(output.stderr).expect("failed to read stderr");
+                assert_eq!(Some(2), output.status.code(), "{} args: {:?}", name, &args);
+                assert!(
+                    err.contains("--index-base"),
+                    "{} stderr, args: {:?} err: {}",
+                    name,
+                    &args,
+                    err
+                );
+            }
+
+            eprintln!("ok");
+        }
+
+        for (name, extra_args, want_success, want) in [
+            (
+                "e2e_number_index_files_union",
+                vec!["--index-line-number"],
+                true,
+                "l1\nl2\nl3\nl5\nl6\n",
+            ),
+            ("e2e_number_index_files_not_number", vec![], false, ""),
+        ] {
+            eprint!("test {} ... ", name);
+
+            let index1_path = tmp_dir.path().join(format!("{}_index1", name));
+            let index2_path = tmp_dir.path().join(format!("{}_index2", name));
+            let target_path = tmp_dir.path().join(format!("{}_target", name));
+            fs::write(&index1_path, "1\n5,6\n").expect("failed to write index");
+            fs::write(&index2_path, "# b\n2,3\n5\n").expect("failed to write index");
+            fs::write(&target_path, "l1\nl2\nl3\nl4\nl5\nl6\nl7\n")
+                .expect("failed to write target");
+
+            let mut args = vec![
+                "--index-file",
+                index1_path.to_str().unwrap(),
+                "--index-file",
+                index2_path.to_str().unwrap(),
+                "--target-file",
+                target_path.to_str().unwrap(),
+            ];
+            args.extend(extra_args);
+            let output = Command::new(bin)
+                .args(args.clone())
+                .output()
+                .expect("failed to run process");
+            assert_eq!(
+                want_success,
+                output.status.success(),
+                "{} status, args: {:?}",
+                name,
+                &args
+            );
+            let got = String::from_utf8(output.stdout).expect("failed to read stdout");
+            assert_eq!(want, got, "{} stdout, args: {:?}", name, &args);
+
+            eprintln!("ok");
+        }
+
+        {
+            let intersect1_path = tmp_dir.path().join("e2e_files_number_intersect_index1");
+            let intersect2_path = tmp_dir.path().join("e2e_files_number_intersect_index2");
+            fs::write(&intersect1_path, "# b\n2,5\n7,\n").expect("failed to write index");
+            fs::write(&intersect2_path, "3,8\n").expect("failed to write index");
+            test_e2e_files!(
+                "e2e_files_number_intersect_index",
+                tmp_dir,
+                bin,
+                [
+                    "--index-line-number",
+                    "--intersect-index",
+                    intersect1_path.to_str().unwrap()
+                ],
+                "1,3\n5\n8,9\n",
+                "l1\nl2\nl3\nl4\nl5\nl6\nl7\nl8\nl9\n",
+                "l2\nl3\nl5\nl8\nl9\n"
+            );
+            test_e2e_files!(
+                "e2e_files_number_intersect_index_multiple",
+                tmp_dir,
+                bin,
+                [
+                    "--index-line-number",
+                    "--intersect-index",
+                    intersect1_path.to_str().unwrap(),
+                    "--intersect-index",
+                    intersect2_path.to_str().unwrap()
+                ],
+                "1,3\n5\n8,9\n",
+                "l1\nl2\nl3\nl4\nl5\nl6\nl7\nl8\nl9\n",
+                "l3\nl5\nl8\n"
+            );
+        }
+
+        {
+            let name = "e2e_files_number_json";
+            eprint!("test {} ... ", name);
+
+            let output = e2e_files_output!(
+                name,
+                tmp_dir,
+                bin,
+                ["--index-line-number", "--json"],
+                "2\n4,5\n",
+                "l1\nl2\nl3\n\"l4\"\t\\\nl5\r\n"
+            );
+            assert!(output.status.success(), "{} status", name);
+            let got = String::from_utf8(output.stdout).expect("failed to read stdout");
+            let got: Vec<(u64, String)> = got
+                .lines()
+                .map(|x| {
+                    let v: serde_json::Value =
+                        serde_json::from_str(x).expect("failed to parse json");
+                    (
+                        v["line"].as_u64().expect("line"),
+                        v["text"].as_str().expect("text").to_string(),
+                    )
+                })
+                .collect();
+            assert_eq!(
+                vec![
+                    (2, "l2".to_string()),
+                    (4, "\"l4\"\t\\".to_string()),
+                    (5, "l5".to_string())
+                ],
+                got,
+                "{} stdout",
+                name
+            );
+
+            eprintln!("ok");
+        }
+        test_e2e_files!(
+            "e2e_files_number_json_input_delimiter",
+            tmp_dir,
+            bin,
+            ["-n", "--json", "--input-delimiter", ";"],
+            "2;",
+            "l1;l\n2;",
+            "{\"line\":2,\"text\":\"l\\n2\"}\n"
+        );
+
+        test_e2e_files!(
+            "e2e_files_number_progress_lines",
+            tmp_dir,
+            bin,
+            ["--index-line-number", "--progress-lines", "2"],
+            "2\n5,6\n",
+            "l1\nl2\nl3\nl4\nl5\nl6\nl7\n",
+            "l2\n# processed 2 lines\n# processed 4 lines\nl5\nl6\n# processed 6 lines\n"
+        );
+        test_e2e_files!(
+            "e2e_files_re_progress_lines_prefix",
+            tmp_dir,
+            bin,
+            ["--progress-lines", "3", "--progress-prefix", "% "],
+            "1\n\n\n1\n\n\n",
+            "l1\nl2\nl3\nl4\nl5\nl6\n",
+            "l1\n% processed 3 lines\nl4\n% processed 6 lines\n"
+        );
+        for (name, extra_args) in [
+            ("e2e_files_progress_lines_null_data", vec!["-Z"]),
+            (
+                "e2e_files_progress_lines_input_delimiter",
+                vec!["--input-delimiter", ";"],
+            ),
+        ] {
+            let mut args = vec!["-n", "--progress-lines", "1"];
+            args.extend(extra_args);
+            test_e2e_files_fail!(
+                name,
+                tmp_dir,
+                bin,
+                args,
+                "1\0",
+                "l1\0",
+                "",
+                "--progress-lines"
+            );
+        }
+
+        test_e2e!(
+            "e2e_each_anchor",
+            tmp_dir,
+            bin,
+            [
+                "--each-anchor",
+                "^a",
+                "--window-before",
+                "1",
+                "--window-after",
+                "1"
+            ],
+            "l1\na2\nl3\nl4\nl5\na6\nl7\na8\nl9\nl10\n",
+            "",
+            "l1\na2\nl3\nl5\na6\nl7\na8\nl9\n"
+        );
+        test_e2e!(
+            "e2e_each_anchor_group_ordinal",
+            tmp_dir,
+            bin,
+            [
+                "--each-anchor",
+                "^a",
+                "--window-after",
+                "2",
+                "--group-ordinal"
+            ],
+            "a1\na2\nl3\nl4\nl5\na6\n",
+            "",
+            "1:a1\n2:a2\n3:l3\n4:l4\n1:a6\n"
+        );
+
+        test_e2e_files!(
+            "e2e_files_number_unique",
+            tmp_dir,
+            bin,
+            ["--index-line-number", "--output-order=index", "--unique"],
+            "1,3\n3\n2,4\n",
+            "l1\nl2\nl3\nl4\n",
+            "l1\nl2\nl3\nl2\nl3\nl4\n"
+        );
+        test_e2e_files!(
+            "e2e_files_number_unique_global",
+            tmp_dir,
+            bin,
+            [
+                "--index-line-number",
+                "--output-order=index",
+                "--unique-global"
+            ],
+            "1,3\n3\n2,4\n",
+            "l1\nl2\nl3\nl4\n",
+            "l1\nl2\nl3\nl4\n"
+        );
+        test_e2e!(
+            "e2e_number_unique_same_content",
+            tmp_dir,
+            bin,
+            ["-L", "1,3", "--unique"],
+            "a\na\nb\n",
+            "",
+            "a\na\nb\n"
+        );
+        test_e2e_files!(
+            "e2e_files_number_unique_global_blank",
+            tmp_dir,
+            bin,
+            ["--index-line-number", "--unique-global"],
+            "1,\n",
+            "a\n\nb\n\n",
+            "a\n\nb\n\n"
+        );
+
+        test_e2e!(
+            "e2e_two_column",
+            tmp_dir,
+            bin,
+            ["--two-column", "^a"],
+            "x0\na1\nx2\nx3\na4\na5\nx6\na7\n",
+            "",
+            "a1\tx2\na4\t\na5\tx6\na7\t\n"
+        );
+        {
+            let name = "e2e_two_column_conflicts";
+            eprint!("test {} ... ", name);
+
+            let target_path = tmp_dir.path().join(format!("{}_target", name));
+            fs::write(&target_path, "a1\nx2\n").expect("failed to write target");
+            for flag in [
+                "--output-number",
+                "--squeeze-blank",
+                "--group-separator",
+                "--output-separator=,",
+                "--output-terminator=.",
+            ] {
+                let args = vec![target_path.to_str().unwrap(), "--two-column", "^a", flag];
+                let output = Command::new(bin)
+                    .args(args.clone())
+                    .output()
+                    .expect("failed to run process");
+                assert_eq!(Some(2), output.status.code(), "{} args: {:?}", name, &args);
+                assert!(
+                    output.stdout.is_empty(),
+                    "{} stdout, args: {:?}",
+                    name,
+                    &args
+                );
+            }
+
+            eprintln!("ok");
+        }
+
+        test_e2e_files!(
+            "e2e_files_re_invert_regex",
+            tmp_dir,
+            bin,
+            ["--invert-regex"],
+            "1\n\n1\n",
+            "l1\nl2\nl3\nl4\n",
+            "l2\nl4\n"
+        );
+        test_e2e_files!(
+            "e2e_files_re_invert_range",
+            tmp_dir,
+            bin,
+            ["--invert-range"],
+            "1\n\n1\n",
+            "l1\nl2\nl3\nl4\n",
+            "l1\nl3\n"
+        );
+        test_e2e_files!(
+            "e2e_files_number_invert_regex",
+            tmp_dir,
+            bin,
+            ["--index-line-number", "--invert-regex"],
+            "2,3\n",
+            "l1\nl2\nl3\nl4\n",
+            "l2\nl3\n"
+        );
+        test_e2e_files!(
+            "e2e_files_number_invert_range",
+            tmp_dir,
+            bin,
+            ["--index-line-number", "--invert-range"],
+            "2,3\n",
+            "l1\nl2\nl3\nl4\n",
+            "l1\nl4\n"
+        );
+
+        test_e2e!(
+            "e2e_byte_span",
+            tmp_dir,
+            bin,
+            ["--byte-span", "2,5"],
+            "abc\ndef\nghi\n",
+            "",
+            "c\nde"
+        );
+        test_e2e!(
+            "e2e_byte_span_beyond_eof",
+            tmp_dir,
+            bin,
+            ["--byte-span", "9,100"],
+            "abc\ndef\nghi\n",
+            "",
+            "hi\n"
+        );
+        test_e2e!(
+            "e2e_byte_span_max",
+            tmp_dir,
+            bin,
+            ["--byte-span", "0,18446744073709551615"],
+            "abc\ndef\n",
+            "",
+            "abc\ndef\n"
+        );
+        {
+            let name = "e2e_byte_span_after_eof";
+            eprint!("test {} ... ", name);
+
+            let target_path = tmp_dir.path().join(format!("{}_target", name));
+            fs::write(&target_path, "abc\n").expect("failed to write target");
+            let args = vec![target_path.to_str().unwrap(), "--byte-span", "4,10"];
+            let output = Command::new(bin)
+                .args(args.clone())
+                .output()
+                .expect("failed to run process");
+            assert_eq!(Some(1), output.status.code(), "{} args: {:?}", name, &args);
+            assert!(
+                output.stdout.is_empty(),
+                "{} stdout, args: {:?}",
+                name,
+                &args
+            );
+
+            eprintln!("ok");
+        }
+
+        test_e2e_files!(
+            "e2e_files_number_zero_based",
+            tmp_dir,
+            bin,
+            ["--index-line-number", "--zero-based"],
+            "0\n2,\n",
+            "l1\nl2\nl3\nl4\n",
+            "l1\nl3\nl4\n"
+        );
+
+        test_e2e!(
+            "e2e_lines",
+            tmp_dir,
+            bin,
+            ["--lines", "1", "--lines", "3,4"],
+            "l1\nl2\nl3\nl4\nl5\n",
+            "",
+            "l1\nl3\nl4\n"
+        );
+
+        {
+            let regex_path = tmp_dir.path().join("e2e_files_regex_file_re");
+            fs::write(&regex_path, "  ^b  \nignored\n").expect("failed to write regex file");
+            test_e2e_files!(
+                "e2e_files_regex_file",
+                tmp_dir,
+                bin,
+                ["--regex-file", regex_path.to_str().unwrap()],
+                "a1\nb2\nc3\nb4\n",
+                "l1\nl2\nl3\nl4\n",
+                "l2\nl4\n"
+            );
+            let missing_path = tmp_dir.path().join("e2e_files_regex_file_missing_re");
+            test_e2e_files_fail!(
+                "e2e_files_regex_file_missing",
+                tmp_dir,
+                bin,
+                ["--regex-file", missing_path.to_str().unwrap()],
+                "a1\n",
+                "l1\n",
+                "",
+                "e2e_files_regex_file_missing_re"
+            );
+        }
+
+        test_e2e_files!(
+            "e2e_files_re_any",
+            tmp_dir,
+            bin,
+            ["--index-regex", "^a", "--index-regex", "^c"],
+            "a1\nb2\nc3\nb4\n",
+            "l1\nl2\nl3\nl4\n",
+            "l1\nl3\n"
+        );
+
+        test_e2e_files!(
+            "e2e_files_line_regexp",
+            tmp_dir,
+            bin,
+            ["--index-regex", "a|b", "-x"],
+            "a\nab\nb\nba\n",
+            "l1\nl2\nl3\nl4\n",
+            "l1\nl3\n"
+        );
+        test_e2e_files!(
+            "e2e_files_line_regexp_anchored",
+            tmp_dir,
+            bin,
+            ["--index-regex", "^a$", "--line-regexp"],
+            "a\nab\nb\na\n",
+            "l1\nl2\nl3\nl4\n",
+            "l1\nl4\n"
+        );
+        test_e2e_files!(
+            "e2e_files_line_regexp_substring",
+            tmp_dir,
+            bin,
+            ["--index-regex", "a|b"],
+            "a\nab\nc\nba\n",
+            "l1\nl2\nl3\nl4\n",
+            "l1\nl2\nl4\n"
+        );
+
+        test_e2e_files!(
+            "e2e_files_re_between",
+            tmp_dir,
+            bin,
+            ["--between", "^start", "^end"],
+            "x\nstart\ny\nend\nz\n",
+            "l1\nl2\nl3\nl4\nl5\n",
+            "l2\nl3\nl4\n"
+        );
+        test_e2e_files!(
+            "e2e_files_re_between_multiple",
+            tmp_dir,
+            bin,
+            ["--between", "^start", "^end"],
+            "start\nend\nx\nstart\ny\nend\nz\nstart\ny\n",
+            "l1\nl2\nl3\nl4\nl5\nl6\nl7\nl8\nl9\n",
+            "l1\nl2\nl4\nl5\nl6\nl8\nl9\n"
+        );
+
+        test_e2e_files!(
+            "e2e_files_re_index_field",
+            tmp_dir,
+            bin,
+            ["--index-regex", "^yes$", "--index-field", "2"],
+            "a\tyes\nyes\tno\nc\tyes\td\nd\n",
+            "l1\nl2\nl3\nl4\n",
+            "l1\nl3\n"
+        );
+        test_e2e_files!(
+            "e2e_files_re_index_field_delimiter",
+            tmp_dir,
+            bin,
+            [
+                "--index-regex",
+                "^yes$",
+                "--index-field",
+                "2",
+                "--index-delimiter",
+                ","
+            ],
+            "a,yes\nyes,no\tyes\n",
+            "l1\nl2\n",
+            "l1\n"
+        );
+
+        test_e2e_files!(
+            "e2e_files_re_from_match",
+            tmp_dir,
+            bin,
+            ["--index-regex", "^a", "--from-match"],
+            "b\na\nb\n",
+            "l1\nl2\nl3\nl4\nl5\n",
+            "l2\nl3\nl4\nl5\n"
+        );
+        test_e2e_files_fail!(
+            "e2e_files_re_from_match_none",
+            tmp_dir,
+            bin,
+            ["--index-regex", "^c", "--from-match"],
+            "b\na\nb\n",
+            "l1\nl2\nl3\n",
+            "",
+            ""
+        );
+
+        test_e2e_files!(
+            "e2e_files_re_all",
+            tmp_dir,
+            bin,
+            ["--index-regex-all", "a", "--index-regex-all", "b"],
+            "ab\nb\nba\na\n",
+            "l1\nl2\nl3\nl4\n",
+            "l1\nl3\n"
+        );
+        test_e2e_files!(
+            "e2e_files_re_all_invert",
+            tmp_dir,
+            bin,
+            ["--index-regex-all", "a", "--index-regex-all", "b", "-v"],
+            "ab\nb\nba\na\n",
+            "l1\nl2\nl3\nl4\n",
+            "l2\nl4\n"
+        );
+
+        test_e2e!(
+            "e2e_each_anchor_color_always",
+            tmp_dir,
+            bin,
+            ["--each-anchor", "b+", "--color=always"],
+            "a\nabbc\nc\n",
+            "",
+            "a\x1b[01;31mbb\x1b[0mc\n"
+        );
+        test_e2e!(
+            "e2e_each_anchor_color_never",
+            tmp_dir,
+            bin,
+            ["--each-anchor", "b+", "--color=never"],
+            "a\nabbc\nc\n",
+            "",
+            "abbc\n"
+        );
+        test_e2e!(
+            "e2e_each_anchor_color_auto",
+            tmp_dir,
+            bin,
+            ["--each-anchor", "b+", "--color"],
+            "a\nabbc\nc\n",
+            "",
+            "abbc\n"
+        );
+
+        {
+            let name = "e2e_target_regex_color_no_color";
+            eprint!("test {} ... ", name);
+
+            let target_path = tmp_dir.path().join(format!("{}_target", name));
+            fs::write(&target_path, "a1\nb2\na3\n").unwrap();
+            for (color, want) in [
+                ("--color=auto", "b2\n"),
+                ("--color=always", "b\x1b[01;31m2\x1b[0m\n"),
+            ] {
+                let args = vec!["--target-regex", "2", color, target_path.to_str().unwrap()];
+                let output = Command::new(bin)
+                    .args(args.clone())
+                    .env("NO_COLOR", "1")
+                    .output()
+                    .expect("failed to run process");
+                assert!(
+                    output.status.success(),
+                    "{} status, args: {:?}",
+                    name,
+                    &args
+                );
+                let got = String::from_utf8(output.stdout).expect("failed to read stdout");
+                assert_eq!(want, got, "{} stdout, args: {:?}", name, &args);
+            }
+
+            eprintln!("ok");
+        }
+
+        test_e2e!(
+            "e2e_target_regex",
+            tmp_dir,
+            bin,
+            ["--target-regex", "^a"],
+            "a1\nb2\na3\n",
+            "",
+            "a1\na3\n"
+        );
+        test_e2e!(
+            "e2e_target_regex_invert",
+            tmp_dir,
+            bin,
+            ["--target-regex", "^a", "-v"],
+            "a1\nb2\na3\n",
+            "",
+            "b2\n"
+        );
+        test_e2e!(
+            "e2e_target_regex_input_delimiter",
+            tmp_dir,
+            bin,
+            ["--target-regex", "x$", "--input-delimiter", ";"],
+            "ax;by;cx;",
+            "",
+            "ax;cx;"
+        );
+        test_e2e!(
+            "e2e_target_regex_null_data",
+            tmp_dir,
+            bin,
+            ["-Z", "--target-regex", "x$"],
+            "ax\0by\0c\nx\0",
+            "",
+            "ax\0c\nx\0"
+        );
+        test_e2e!(
+            "e2e_target_regex_color_always",
+            tmp_dir,
+            bin,
+            ["--target-regex", "2", "--color=always"],
+            "a1\nb2\na3\n",
+            "",
+            "b\x1b[01;31m2\x1b[0m\n"
+        );
+
+        test_e2e_files!(
+            "e2e_files_re_show_index",
+            tmp_dir,
+            bin,
+            ["--index-regex", "^a", "--show-index"],
+            "a1\nb2\na3\n",
+            "l1\nl2\nl3\n",
+            "a1\tl1\na3\tl3\n"
+        );
+
+        test_e2e_files!(
+            "e2e_files_fields",
+            tmp_dir,
+            bin,
+            ["--index-line-number", "--fields", "1,3"],
+            "1,2\n",
+            "a\tb\tc\nd\te\tf\ng\th\ti\n",
+            "a\tc\nd\tf\n"
+        );
+        test_e2e_files!(
+            "e2e_files_fields_delimiter",
+            tmp_dir,
+            bin,
+            ["--index-line-number", "-f", "3,1", "-d", ","],
+            "2\n",
+            "a,b,c\nd,e,f\n",
+            "f,d\n"
+        );
+
+        test_e2e_files!(
+            "e2e_files_number_input_delimiter_nul",
+            tmp_dir,
+            bin,
+            ["--index-line-number", "--input-delimiter", "\\0"],
+            "2,3\0",
+            "l1\0l2\nx\0l3\0l4\0",
+            "l2\nx\0l3\0"
+        );
+        test_e2e_files!(
+            "e2e_files_number_null_data",
+            tmp_dir,
+            bin,
+            ["--index-line-number", "-Z"],
+            "1\0\x003,\0",
+            "l1\0l2\nx\0l3\0l4",
+            "l1\0l3\0l4\0"
+        );
+        test_e2e_files!(
+            "e2e_files_re_null_data",
+            tmp_dir,
+            bin,
+            ["--index-regex", "^a$", "--null-data", "--end-marker", "END"],
+            "a\0a\nb\0a\0",
+            "l1\0l2\0l3\0",
+            "l1\0l3\0END\0"
+        );
+        test_e2e!(
+            "e2e_lines_input_delimiter",
+            tmp_dir,
+            bin,
+            ["--lines", "1", "--lines", "3", "--input-delimiter", ";"],
+            "a;b;c;d",
+            "",
+            "a;c;"
+        );
+
+        test_e2e_files!(
+            "e2e_files_number_last_line_without_newline",
+            tmp_dir,
+            bin,
+            ["--index-line-number"],
+            "2\n",
+            "l1\nl2",
+            "l2"
+        );
+        test_e2e_files!(
+            "e2e_files_number_last_line_without_newline_end_marker",
+            tmp_dir,
+            bin,
+            ["--index-line-number", "--end-marker", "__END__"],
+            "2\n",
+            "l1\nl2",
+            "l2\n__END__\n"
+        );
+
+        for (name, index, want_hint) in [
+            ("e2e_files_number_swapped_warning", "l1\nl2\n", true),
+            (
+                "e2e_files_number_swapped_warning_later_range",
+                "l1\n2\n",
+                false,
+            ),
+        ] {
+            eprint!("test {} ... ", name);
+
+            let index_path = tmp_dir.path().join(format!("{}_index", name));
+            let target_path = tmp_dir.path().join(format!("{}_target", name));
+            fs::write(&index_path, index).expect("failed to write index");
+            fs::write(&target_path, "2\n").expect("failed to write target");
+
+            let args = vec![
+                "--index-line-number",
+                index_path.to_str().unwrap(),
+                target_path.to_str().unwrap(),
+            ];
+            let output = Command::new(bin)
+                .args(args.clone())
+                .env_remove("RUST_LOG")
+                .output()
+                .expect("failed to run process");
+            assert!(
+                !output.status.success(),
+                "{} status, args: {:?}",
+                name,
+                &args
+            );
+            let err = String::from_utf8(output.stderr).expect("failed to read stderr");
+            assert_eq!(
+                want_hint,
+                err.contains("--swap-file-role"),
+                "{} stderr, args: {:?} err: {}",
+                name,
+                &args,
+                err
+            );
+
+            eprintln!("ok");
+        }
+
+        for (name, extra_args, index, want_out, want_err) in [
+            (
+                "e2e_files_number_warn_overshoot",
+                vec![],
+                "10\n",
+                "",
+                "beyond the end of TARGET at line 3: 10 (index line 1)",
+            ),
+            (
+                "e2e_files_number_warn_overshoot_rest",
+                vec![],
+                "2\n\n4,5\n10\n",
+                "l2\n",
+                "beyond the end of TARGET at line 3: 4,5 (index line 3), 10 (index line 4)",
+            ),
+            (
+                "e2e_files_number_warn_overshoot_index_base",
+                vec!["--index-base", "5"],
+                "2\n20\n30\n",
+                "",
+                "beyond the end of TARGET at line 3: 7 (index line 1), 25 (index line 2), 35 (index line 3)",
+            ),
+            (
+                "e2e_files_number_warn_overshoot_relative",
+                vec![],
+                "1\n+20\n+5\n",
+                "l1\n",
+                "beyond the end of TARGET at line 3: 21 (index line 2), 26 (index line 3)",
+            ),
+        ] {
+            eprint!("test {} ... ", name);
+
+            let index_path = tmp_dir.path().join(format!("{}_index", name));
+            let target_path = tmp_dir.path().join(format!("{}_target", name));
+            fs::write(&index_path, index).expect("failed to write index");
+            fs::write(&target_path, "l1\nl2\nl3\n").expect("failed to write target");
+
+            let mut args = vec!["--index-line-number", "--warn-overshoot"];
+            args.extend(extra_args);
+            args.extend([index_path.to_str().unwrap(), target_path.to_str().unwrap()]);
+            let output = Command::new(bin)
+                .args(args.clone())
+                .env_remove("RUST_LOG")
+                .output()
+                .expect("failed to run process");
+            let got = String::from_utf8(output.stdout).expect("failed to read stdout");
+            let err = String::from_utf8(output.stderr).expect("failed to read stderr");
+            assert_eq!(want_out, got, "{} stdout, args: {:?}", name, &args);
+            assert!(
+                err.contains(want_err),
+                "{} stderr, args: {:?} err: {}",
+                name,
+                &args,
+                err
+            );
+
+            eprintln!("ok");
+        }
+
+        for (name, args, value, want_out, want_err) in [
+            (
+                "e2e_index_env_number",
+                vec!["--index-line-number"],
+                Some("1\\n3,"),
+                "l1\nl3\nl4\n",
+                "",
+            ),
+            (
+                "e2e_index_env_re",
+                vec!["-e", "^a"],
+                Some("a\nb\na\n"),
+                "l1\nl3\n",
+                "",
+            ),
+            (
+                "e2e_index_env_empty",
+                vec!["--index-line-number"],
+                Some(""),
+                "",
+                "E2E_LISEL_INDEX: environment variable is empty",
+            ),
+            (
+                "e2e_index_env_not_set",
+                vec!["--index-line-number"],
+                None,
+                "",
+                "E2E_LISEL_INDEX: environment variable is not set",
+            ),
+        ] {
+            eprint!("test {} ... ", name);
+
+            let target_path = tmp_dir.path().join(format!("{}_target", name));
+            fs::write(&target_path, "l1\nl2\nl3\nl4\n").expect("failed to write target");
+
+            let mut args = args;
+            args.extend([
+                "--index-env",
+                "E2E_LISEL_INDEX",
+                target_path.to_str().unwrap(),
+            ]);
+            let mut cmd = Command::new(bin);
+            cmd.args(args.clone());
+            match value {
+                Some(x) => cmd.env("E2E_LISEL_INDEX", x),
+                None => cmd.env_remove("E2E_LISEL_INDEX"),
+            };
+            let output = cmd.output().expect("failed to run process");
+            let got = String::from_utf8(output.stdout).expect("failed to read stdout");
+            let err = String::from_utf8(output.stderr).expect("failed to read stderr");
+            assert_eq!(want_out, got, "{} stdout, args: {:?}", name, &args);
+            assert_eq!(
+                want_err.is_empty(),
+                output.status.success(),
+                "{} status, args: {:?} err: {}",
+                name,
+                &args,
+                err
+            );
+            assert!(
+                err.contains(want_err),
+                "{} stderr, args: {:?} err: {}",
+                name,
+                &args,
+                err
+            );
+
+            eprintln!("ok");
+        }
+
+        {
+            let name = "e2e_files_number_stats";
+            eprint!("test {} ... ", name);
+
+            let output = e2e_files_output!(
+                name,
+                tmp_dir,
+                bin,
+                ["--index-line-number", "--stats"],
+                "2\n4\n",
+                "l1\nl2\nl3\nl4\nl5\n"
+            );
+            assert!(output.status.success(), "{} status", name);
+            assert_eq!(b"l2\nl4\n".to_vec(), output.stdout, "{} stdout", name);
+            let err = String::from_utf8(output.stderr).expect("failed to read stderr");
+            let stats: Vec<(&str, u64)> = err
+                .trim_end()
+                .split(' ')
+                .map(|x| {
+                    let (k, v) = x.split_once('=').expect("stats want key=value");
+                    (k, v.parse().expect("stats want number"))
+                })
+                .collect();
+            assert_eq!(
+                vec![("target", 5), ("index", 2), ("emitted", 2)],
+                stats,
+                "{} stderr: {}",
+                name,
+                err
+            );
+
+            eprintln!("ok");
+        }
+
+        test_e2e_files!(
+            "e2e_files_chars",
+            tmp_dir,
+            bin,
+            ["--index-line-number", "--chars", "2,3"],
+            "1,3\n",
+            "aあいう\nx\nαβγδ\n",
+            "あい\n\nβγ\n"
+        );
+        test_e2e_files!(
+            "e2e_files_chars_right_open",
+            tmp_dir,
+            bin,
+            ["--index-line-number", "--chars", "3,"],
+            "1\n",
+            "日本語です\n",
+            "語です\n"
+        );
+
+        for (name, flag, want) in [
+            ("e2e_files_trim", "--trim", "  a\n\tb\n  c\r\n"),
+            ("e2e_files_trim_start", "--trim-start", "a  \nb \t\nc \r\n"),
+            ("e2e_files_trim_both", "--trim-both", "a\nb\nc\r\n"),
+        ] {
+            test_e2e_files!(
+                name,
+                tmp_dir,
+                bin,
+                ["--index-line-number", flag],
+                "1,3\n",
+                "  a  \n\tb \t\n  c \r\n",
+                want
+            );
+        }
+        test_e2e_files!(
+            "e2e_files_trim_null_data",
+            tmp_dir,
+            bin,
+            ["--index-line-number", "--null-data", "--trim-both"],
+            "1\x002\x00",
+            " a \x00 b ",
+            "a\x00b\x00"
+        );
+
+        test_e2e_files!(
+            "e2e_files_squeeze_blank",
+            tmp_dir,
+            bin,
+            ["--index-line-number", "--squeeze-blank"],
+            "1,6\n8,10\n",
+            "l1\n\n\n\nl5\n\n\n\n\nl10\n",
+            "l1\n\nl5\n\nl10\n"
+        );
+        test_e2e_files!(
+            "e2e_files_squeeze_blank_input_delimiter",
+            tmp_dir,
+            bin,
+            ["-n", "--squeeze-blank", "--input-delimiter", ";"],
+            "1,5;",
+            "l1;;;\n;l5;",
+            "l1;;\n;l5;"
+        );
+        test_e2e_files!(
+            "e2e_files_squeeze_blank_null_data",
+            tmp_dir,
+            bin,
+            ["-n", "--squeeze-blank", "-Z"],
+            "1,4\0",
+            "l1\0\0\0l4\0",
+            "l1\0\0l4\0"
+        );
+        test_e2e_files!(
+            "e2e_files_squeeze_blank_group_separator",
+            tmp_dir,
+            bin,
+            [
+                "--index-line-number",
+                "--squeeze-blank",
+                "--group-separator"
+            ],
+            "1,4\n6,8\n",
+            "l1\n\n\n\nl5\n\n\nl8\n",
+            "l1\n\n--\n\nl8\n"
+        );
+        test_e2e!(
+            "e2e_each_anchor_squeeze_blank",
+            tmp_dir,
+            bin,
+            [
+                "--each-anchor",
+                "^a",
+                "--window-before",
+                "2",
+                "--squeeze-blank"
+            ],
+            "\n\na1\nb\n\n\na2\n",
+            "",
+            "\na1\n\na2\n"
+        );
+
+        test_e2e_files!(
+            "e2e_files_byte_offset",
+            tmp_dir,
+            bin,
+            ["--index-line-number", "--byte-offset"],
+            "1\n3,4\n",
+            "l1\nline 2\r\nあい\nl4",
+            "0:l1\n11:あい\n18:l4"
+        );
+        test_e2e_files!(
+            "e2e_files_byte_offset_re",
+            tmp_dir,
+            bin,
+            ["-b"],
+            "\nx\n\nx\n",
+            "abc\nde\nf\ng\n",
+            "4:de\n9:g\n"
+        );
+
+        for (name, args, want) in [
+            (
+                "e2e_files_invert_accept_after_index",
+                vec!["-v"],
+                "l2\nl4\nl5\n",
+            ),
+            (
+                "e2e_files_invert_no_accept_after_index",
+                vec!["-v", "--no-accept-after-index"],
+                "l2\n",
+            ),
+            (
+                "e2e_files_number_invert_no_accept_after_index",
+                vec!["-n", "--invert-range", "--no-accept-after-index"],
+                "l2\n",
+            ),
+        ] {
+            let index = if name.contains("number") {
+                "1\n3\n"
+            } else {
+                "x\n\nx\n"
+            };
+            test_e2e_files!(
+                name,
+                tmp_dir,
+                bin,
+                args,
+                index,
+                "l1\nl2\nl3\nl4\nl5\n",
+                want
+            );
+        }
+
+        test_e2e_files!(
+            "e2e_files_number_output_separator",
+            tmp_dir,
+            bin,
+            ["--index-line-number", "--output-separator", ", "],
+            "1\n3,4\n",
+            "l1\nl2\nl3\r\nl4",
+            "l1, l3, l4\n"
+        );
+        test_e2e_files!(
+            "e2e_files_number_output_separator_terminator",
+            tmp_dir,
+            bin,
+            [
+                "--index-line-number",
+                "--output-separator=|",
+                "--output-terminator=.",
+                "--end-marker",
+                "END"
+            ],
+            "2,3\n",
+            "l1\nl2\nl3\n",
+            "l2|l3.\nEND\n"
+        );
+        test_e2e_files!(
+            "e2e_files_number_output_terminator",
+            tmp_dir,
+            bin,
+            ["--index-line-number", "--output-terminator", ";\n"],
+            "1\n2\n",
+            "l1\nl2\n",
+            "l1\nl2;\n"
+        );
+
+        test_e2e_files!(
+            "e2e_files_cycle_index",
+            tmp_dir,
+            bin,
+            ["--cycle-index"],
+            "x\n\n",
+            "l1\nl2\nl3\nl4\nl5\nl6\n",
+            "l1\nl3\nl5\n"
+        );
+        test_e2e_files!(
+            "e2e_files_cycle_index_invert",
+            tmp_dir,
+            bin,
+            ["--cycle-index", "-e", "^a", "-v"],
+            "a\nb\nb\n",
+            "l1\nl2\nl3\nl4\nl5\nl6\nl7\n",
+            "l2\nl3\nl5\nl6\n"
+        );
 
-        let tmp_dir = TempDir::new_in(".").unwrap();
+        test_e2e_files!(
+            "e2e_files_regex_timeout_within",
+            tmp_dir,
+            bin,
+            ["-e", "^a", "--regex-timeout", "60000"],
+            "a1\nb2\na3\n",
+            "l1\nl2\nl3\n",
+            "l1\nl3\n"
+        );
 
-        test_e2e!(
-            "e2e_re_default",
+        test_e2e_files!(
+            "e2e_files_number_skip_blank_target",
             tmp_dir,
             bin,
-            vec![],
-            "1\n\n1\n",
-            "l1\nl2\nl3\nl4\nl5\n",
+            ["--index-line-number", "--skip-blank-target", "--json"],
+            "2\n4,\n",
+            "l1\n\nl2\n\n\nl3\nl4\n\nl5\n",
+            "{\"line\":2,\"text\":\"l2\"}\n{\"line\":4,\"text\":\"l4\"}\n{\"line\":5,\"text\":\"l5\"}\n"
+        );
+        test_e2e_files!(
+            "e2e_files_number_invert_skip_blank_target",
+            tmp_dir,
+            bin,
+            ["--index-line-number", "--skip-blank-target", "-v"],
+            "2\n",
+            "\nl1\n\nl2\nl3\n\n",
+            "l1\nl3\n"
+        );
+
+        test_e2e_files!(
+            "e2e_files_number_output_order_index",
+            tmp_dir,
+            bin,
+            ["--index-line-number", "--output-order=index"],
+            "3\n1\n3\n",
+            "l1\nl2\nl3\nl4\n",
+            "l3\nl1\nl3\n"
+        );
+        test_e2e_files!(
+            "e2e_files_number_output_order_index_ranges",
+            tmp_dir,
+            bin,
+            ["--index-line-number", "--output-order", "index"],
+            "3,\n1,2\n+1\n",
+            "l1\nl2\nl3\nl4\n",
+            "l3\nl4\nl1\nl2\nl3\n"
+        );
+        for (name, flag, want_out, want_err) in [
+            (
+                "e2e_files_number_output_order_index_quiet",
+                "--quiet",
+                "",
+                "",
+            ),
+            (
+                "e2e_files_number_output_order_index_stats",
+                "--stats",
+                "l3\nl1\n",
+                "target=4 index=2 emitted=2\n",
+            ),
+        ] {
+            eprint!("test {} ... ", name);
+
+            let output = e2e_files_output!(
+                name,
+                tmp_dir,
+                bin,
+                ["--index-line-number", "--output-order=index", flag],
+                "3\n1\n",
+                "l1\nl2\nl3\nl4\n"
+            );
+            assert!(output.status.success(), "{} status", name);
+            let got = String::from_utf8(output.stdout).expect("failed to read stdout");
+            let err = String::from_utf8(output.stderr).expect("failed to read stderr");
+            assert_eq!(want_out, got, "{} stdout", name);
+            assert_eq!(want_err, err, "{} stderr", name);
+
+            eprintln!("ok");
+        }
+        test_e2e_files!(
+            "e2e_files_number_output_order_target",
+            tmp_dir,
+            bin,
+            ["--index-line-number", "--output-order=target"],
+            "1\n3\n",
+            "l1\nl2\nl3\nl4\n",
+            "l1\nl3\n"
+        );
+
+        test_e2e_files!(
+            "e2e_files_number_index_base",
+            tmp_dir,
+            bin,
+            ["--index-line-number", "--index-base", "1"],
+            "0\n2,3\n5,\n",
+            "l1\nl2\nl3\nl4\nl5\nl6\nl7\n",
+            "l1\nl3\nl4\nl6\nl7\n"
+        );
+        test_e2e_files!(
+            "e2e_files_number_index_base_zero_start",
+            tmp_dir,
+            bin,
+            ["--index-line-number", "--index-base", "5"],
+            "0,3\n",
+            "l1\nl2\nl3\nl4\nl5\nl6\nl7\nl8\nl9\nl10\n",
+            "l5\nl6\nl7\nl8\n"
+        );
+        test_e2e_files!(
+            "e2e_files_number_index_base_negative",
+            tmp_dir,
+            bin,
+            ["--index-line-number", "--index-base", "-1"],
+            "2\n4\n",
+            "l1\nl2\nl3\nl4\n",
             "l1\nl3\n"
         );
+        test_e2e_files_fail!(
+            "e2e_files_number_overflow",
+            tmp_dir,
+            bin,
+            ["--index-line-number"],
+            "1\n99999999999\n",
+            "l1\nl2\n",
+            "l1\n",
+            "99999999999"
+        );
+        test_e2e_files_fail!(
+            "e2e_files_number_index_base_out_of_line_numbers",
+            tmp_dir,
+            bin,
+            ["--index-line-number", "--index-base", "-2"],
+            "3\n1\n",
+            "l1\nl2\nl3\n",
+            "l1\n",
+            "out of line numbers"
+        );
+
         test_e2e!(
-            "e2e_re_default_invert",
+            "e2e_head",
             tmp_dir,
             bin,
-            vec!["--index-invert-match"],
-            "1\n\n1\n",
+            ["--head", "2"],
             "l1\nl2\nl3\nl4\nl5\n",
-            "l2\nl4\nl5\n"
+            "",
+            "l1\nl2\n"
         );
         test_e2e!(
-            "e2e_re_default_swap",
+            "e2e_tail",
             tmp_dir,
             bin,
-            vec!["--swap-file-role"],
+            ["--tail", "2"],
             "l1\nl2\nl3\nl4\nl5\n",
-            "1\n\n1\n",
-            "l1\nl3\n"
+            "",
+            "l4\nl5\n"
+        );
+        test_e2e!(
+            "e2e_tail_longer_than_target",
+            tmp_dir,
+            bin,
+            ["--tail", "9"],
+            "l1\nl2",
+            "",
+            "l1\nl2"
+        );
+
+        test_e2e!(
+            "e2e_index_arg",
+            tmp_dir,
+            bin,
+            [
+                "-n",
+                "--index-arg",
+                "1",
+                "--index-arg",
+                "3,5",
+                "--index-arg",
+                "7,"
+            ],
+            "l1\nl2\nl3\nl4\nl5\nl6\nl7\nl8\n",
+            "",
+            "l1\nl3\nl4\nl5\nl7\nl8\n"
+        );
+        test_e2e_files_fail!(
+            "e2e_index_arg_invalid",
+            tmp_dir,
+            bin,
+            ["--index-arg", "1", "--index-arg", "x"],
+            "l1\n",
+            "l1\n",
+            "",
+            "x: Parsing Error"
+        );
+
+        for (name, index, want) in [
+            ("e2e_files_number_exit_status_selected", "2\n", Some(0)),
+            ("e2e_files_number_exit_status_not_selected", "5\n", Some(1)),
+            ("e2e_files_number_exit_status_error", "x\n", Some(2)),
+        ] {
+            eprint!("test {} ... ", name);
+
+            let output = e2e_files_output!(
+                name,
+                tmp_dir,
+                bin,
+                ["--index-line-number"],
+                index,
+                "l1\nl2\n"
+            );
+            assert_eq!(want, output.status.code(), "{} status", name);
+
+            eprintln!("ok");
+        }
+
+        {
+            let name = "e2e_explicit_files";
+            let index_path = tmp_dir.path().join(format!("{}_index", name));
+            let target_path = tmp_dir.path().join(format!("{}_target", name));
+            fs::write(&index_path, "2\n4\n").expect("failed to write index");
+            fs::write(&target_path, "l1\nl2\nl3\nl4\n").expect("failed to write target");
+
+            for (name, args, input, want) in [
+                (
+                    "e2e_explicit_files_index_stdin",
+                    vec!["-n", "--target-file", target_path.to_str().unwrap()],
+                    "1\n3\n",
+                    "l1\nl3\n",
+                ),
+                (
+                    "e2e_explicit_files_target_stdin",
+                    vec!["-n", "--index-file", index_path.to_str().unwrap()],
+                    "m1\nm2\nm3\nm4\n",
+                    "m2\nm4\n",
+                ),
+                (
+                    "e2e_explicit_files_over_positional",
+                    vec![
+                        "-n",
+                        "--index-file",
+                        index_path.to_str().unwrap(),
+                        "--target-file",
+                        target_path.to_str().unwrap(),
+                        target_path.to_str().unwrap(),
+                    ],
+                    "",
+                    "l2\nl4\n",
+                ),
+            ] {
+                eprint!("test {} ... ", name);
+
+                let mut process = Command::new(bin)
+                    .args(args.clone())
+                    .stdin(Stdio::piped())
+                    .stdout(Stdio::piped())
+                    .spawn()
+                    .expect("failed to spawn process");
+                if let Some(ref mut stdin) = process.stdin {
+                    stdin
+                        .write_all(input.as_bytes())
+                        .expect("failed to write data to stdin");
+                }
+                let output = process.wait_with_output().expect("failed to wait process");
+                assert!(
+                    output.status.success(),
+                    "{} status, args: {:?}",
+                    name,
+                    &args
+                );
+                let got = String::from_utf8(output.stdout).expect("failed to read stdout");
+                assert_eq!(want, got, "{} stdout, args: {:?}", name, &args);
+
+                eprintln!("ok");
+            }
+        }
+
+        test_e2e_files_fail!(
+            "e2e_files_number_strict_range",
+            tmp_dir,
+            bin,
+            ["--index-line-number", "--strict-range"],
+            "1\n3,2\n",
+            "l1\nl2\nl3\n",
+            "l1\n",
+            "start 3 is greater than end 2"
         );
 
         test_e2e_files!(
-            "e2e_files_re_default",
+            "e2e_files_number_rest",
             tmp_dir,
             bin,
-            vec![],
-            "1\n\n1\n",
+            ["--index-line-number", "--rest"],
+            "3\n",
             "l1\nl2\nl3\nl4\nl5\n",
-            "l1\nl3\n"
+            "l3\nl4\nl5\n"
         );
         test_e2e_files!(
-            "e2e_files_re",
+            "e2e_files_number_rest_invert",
             tmp_dir,
             bin,
-            vec!["--index-regex", "^$"],
-            "1\n\n1\n",
+            ["--index-line-number", "--rest", "--invert-range"],
+            "3\n",
             "l1\nl2\nl3\nl4\nl5\n",
-            "l2\n"
+            "l1\nl2\n"
         );
-        test_e2e_files!(
-            "e2e_files_re_invert",
+        test_e2e!(
+            "e2e_number_lines_rest",
             tmp_dir,
             bin,
-            vec!["--index-regex", "^$", "--index-invert-match"],
-            "1\n\n1\n",
+            ["-L", "2", "--rest"],
+            "l1\nl2\nl3\n",
+            "",
+            "l2\nl3\n"
+        );
+        test_e2e_files_fail!(
+            "e2e_files_re_rest",
+            tmp_dir,
+            bin,
+            ["--rest"],
+            "a\n",
+            "l1\nl2\n",
+            "",
+            "--rest requires a line number index"
+        );
+
+        test_e2e!(
+            "e2e_every",
+            tmp_dir,
+            bin,
+            ["--every", "2"],
+            "l1\nl2\nl3\nl4\nl5\nl6\n",
+            "",
+            "l2\nl4\nl6\n"
+        );
+        test_e2e!(
+            "e2e_every_invert",
+            tmp_dir,
+            bin,
+            ["--every", "2", "--index-invert-match"],
+            "l1\nl2\nl3\nl4\nl5\nl6\n",
+            "",
+            "l1\nl3\nl5\n"
+        );
+
+        {
+            let name = "e2e_sample";
+            eprint!("test {} ... ", name);
+
+            let target_path = tmp_dir.path().join(format!("{}_target", name));
+            let target: String = (1..=1000).map(|i| format!("l{}\n", i)).collect();
+            fs::write(&target_path, target).expect("failed to write target");
+            let outputs: Vec<String> = ["1", "1", "2"]
+                .iter()
+                .map(|seed| {
+                    let output = Command::new(bin)
+                        .args([
+                            target_path.to_str().unwrap(),
+                            "--sample",
+                            "0.3",
+                            "--seed",
+                            seed,
+                        ])
+                        .output()
+                        .expect("failed to run process");
+                    assert!(output.status.success(), "{} status", name);
+                    String::from_utf8(output.stdout).expect("failed to read stdout")
+                })
+                .collect();
+            assert_eq!(outputs[0], outputs[1], "{} same seed", name);
+            assert_ne!(outputs[0], outputs[2], "{} different seed", name);
+            let count = outputs[0].lines().count();
+            assert!((200..=400).contains(&count), "{} count: {}", name, count);
+
+            eprintln!("ok");
+        }
+
+        test_e2e!(
+            "e2e_sample_null_data",
+            tmp_dir,
+            bin,
+            ["-Z", "--sample", "1"],
+            "l1\0l2\nl3\0",
+            "",
+            "l1\0l2\nl3\0"
+        );
+        {
+            let name = "e2e_sample_input_delimiter";
+            eprint!("test {} ... ", name);
+
+            let target_path = tmp_dir.path().join(format!("{}_target", name));
+            let target: String = (1..=1000).map(|i| format!("l{};", i)).collect();
+            fs::write(&target_path, target).expect("failed to write target");
+            let output = Command::new(bin)
+                .args([
+                    target_path.to_str().unwrap(),
+                    "--input-delimiter",
+                    ";",
+                    "--sample",
+                    "0.5",
+                    "--seed",
+                    "1",
+                ])
+                .output()
+                .expect("failed to run process");
+            assert!(output.status.success(), "{} status", name);
+            let got = String::from_utf8(output.stdout).expect("failed to read stdout");
+            let count = got.matches(';').count();
+            assert!((400..=600).contains(&count), "{} count: {}", name, count);
+
+            eprintln!("ok");
+        }
+        {
+            let name = "e2e_sample_stats";
+            eprint!("test {} ... ", name);
+
+            let target_path = tmp_dir.path().join(format!("{}_target", name));
+            fs::write(&target_path, "l1\nl2\nl3\n").expect("failed to write target");
+            let output = Command::new(bin)
+                .args([target_path.to_str().unwrap(), "--sample", "1", "--stats"])
+                .output()
+                .expect("failed to run process");
+            assert!(output.status.success(), "{} status", name);
+            let got = String::from_utf8(output.stdout).expect("failed to read stdout");
+            let err = String::from_utf8(output.stderr).expect("failed to read stderr");
+            assert_eq!("l1\nl2\nl3\n", got, "{} stdout", name);
+            assert_eq!("target=3 index=0 emitted=3\n", err, "{} stderr", name);
+
+            eprintln!("ok");
+        }
+
+        for (name, args, index) in [
+            (
+                "e2e_files_re_count_unselected",
+                vec!["--index-regex", "^a"],
+                "a\nb\na\nb\na\n",
+            ),
+            (
+                "e2e_files_number_count_unselected",
+                vec!["--index-line-number"],
+                "2,3\n5\n",
+            ),
+        ] {
+            eprint!("test {} ... ", name);
+
+            let target = "l1\nl2\nl3\nl4\nl5\nl6\nl7\n";
+            let output = e2e_files_output!(name, tmp_dir, bin, args, index, target);
+            assert!(output.status.success(), "{} status", name);
+            let selected = String::from_utf8(output.stdout)
+                .expect("failed to read stdout")
+                .lines()
+                .count();
+            let mut count_args = args.clone();
+            count_args.push("--count-unselected");
+            let output = e2e_files_output!(name, tmp_dir, bin, count_args, index, target);
+            assert!(output.status.success(), "{} status", name);
+            let unselected: usize = String::from_utf8(output.stdout)
+                .expect("failed to read stdout")
+                .trim_end()
+                .parse()
+                .expect("want a count");
+            assert_eq!(
+                target.lines().count(),
+                selected + unselected,
+                "{} selected {} unselected {}",
+                name,
+                selected,
+                unselected
+            );
+
+            eprintln!("ok");
+        }
+
+        test_e2e!(
+            "e2e_head_percent",
+            tmp_dir,
+            bin,
+            ["--head-percent", "30"],
+            "l1\nl2\nl3\nl4\nl5\nl6\nl7\nl8\nl9\nl10\n",
+            "",
+            "l1\nl2\nl3\n"
+        );
+        test_e2e!(
+            "e2e_head_percent_round_up",
+            tmp_dir,
+            bin,
+            ["--head-percent", "30"],
             "l1\nl2\nl3\nl4\nl5\n",
-            "l1\nl3\nl4\nl5\n"
+            "",
+            "l1\nl2\n"
         );
+
         test_e2e_files!(
-            "e2e_files_re_default_swap",
+            "e2e_files_explain_number",
             tmp_dir,
             bin,
-            vec!["--swap-file-role"],
-            "l1\nl2\nl3\nl4\nl5\n",
-            "1\n\n1\n",
-            "l1\nl3\n"
+            ["--index-line-number", "--explain"],
+            "1\n3,4\n\n+2\n8,\n,2\n",
+            "l1\n",
+            "index line 1 -> target line 1\nindex line 2 -> target lines 3..4\nindex line 3 -> ignored\nindex line 4 -> target line 6\nindex line 5 -> target lines 8..end\nindex line 6 -> target lines 1..2\n"
         );
         test_e2e_files!(
-            "e2e_files_number",
+            "e2e_files_explain_re",
             tmp_dir,
             bin,
-            vec!["--index-line-number"],
-            "1\n3,4\n",
-            "l1\nl2\nl3\nl4\nl5\n",
-            "l1\nl3\nl4\n"
+            ["--explain"],
+            "a\n\nb\n",
+            "l1\n",
+            "index line 1 -> target line 1\nindex line 2 -> no target lines\nindex line 3 -> target line 3\n"
+        );
+
+        {
+            let name = "e2e_validate";
+            eprint!("test {} ... ", name);
+
+            let index_path = tmp_dir.path().join(format!("{}_index", name));
+            fs::write(&index_path, "1\n3,5\n\n4\nx\n# comment\n7,6\n8,\n").unwrap();
+            let output = Command::new(bin)
+                .args(["--validate", index_path.to_str().unwrap()])
+                .output()
+                .expect("failed to run process");
+            assert_eq!(Some(2), output.status.code(), "{} status", name);
+            assert!(output.stdout.is_empty(), "{} stdout", name);
+            let err = String::from_utf8(output.stderr).expect("failed to read stderr");
+            let index_name = index_path.to_str().unwrap();
+            for want in [
+                format!("{}:3: empty line", index_name),
+                format!(
+                    "{}:4: 4: not in ascending order, overlaps line 2",
+                    index_name
+                ),
+                format!("{}:5: x:", index_name),
+                format!("{}:7: 7,6: start is greater than end", index_name),
+                format!("{}: 4 problems found", index_name),
+            ] {
+                assert!(
+                    err.contains(&want),
+                    "{} stderr want {} got {}",
+                    name,
+                    want,
+                    err
+                );
+            }
+            assert!(!err.contains(":1:"), "{} stderr: {}", name, err);
+            assert!(!err.contains(":8:"), "{} stderr: {}", name, err);
+
+            fs::write(&index_path, "1\n3,5\n# comment\n6\n+2\n9,\n").unwrap();
+            let output = Command::new(bin)
+                .args(["--validate", index_path.to_str().unwrap()])
+                .output()
+                .expect("failed to run process");
+            assert!(output.status.success(), "{} valid status", name);
+            assert!(output.stderr.is_empty(), "{} valid stderr", name);
+
+            eprintln!("ok");
+        }
+
+        {
+            let name = "e2e_multiple_targets";
+            eprint!("test {} ... ", name);
+
+            let index_path = tmp_dir.path().join(format!("{}_index", name));
+            let t1_path = tmp_dir.path().join(format!("{}_t1", name));
+            let t2_path = tmp_dir.path().join(format!("{}_t2", name));
+            fs::write(&index_path, "2\n3\n").unwrap();
+            fs::write(&t1_path, "a1\na2\na3\n").unwrap();
+            fs::write(&t2_path, "b1\nb2\nb3\n").unwrap();
+            for (args, want) in [
+                (vec!["-n"], "a2\na3\nb2\nb3\n"),
+                (vec!["-n", "--continuous"], "a2\na3\n"),
+                (
+                    vec!["-n", "--progress-lines", "2"],
+                    "a2\n# processed 2 lines\na3\n# processed 4 lines\nb2\nb3\n# processed 6 lines\n",
+                ),
+                (
+                    vec!["-n", "--json"],
+                    "{\"line\":2,\"text\":\"a2\"}\n{\"line\":3,\"text\":\"a3\"}\n{\"line\":2,\"text\":\"b2\"}\n{\"line\":3,\"text\":\"b3\"}\n",
+                ),
+            ] {
+                let mut args = args;
+                args.extend([
+                    index_path.to_str().unwrap(),
+                    t1_path.to_str().unwrap(),
+                    t2_path.to_str().unwrap(),
+                ]);
+                let output = Command::new(bin)
+                    .args(args.clone())
+                    .output()
+                    .expect("failed to run process");
+                assert!(output.status.success(), "{} status, args: {:?}", name, &args);
+                let got = String::from_utf8(output.stdout).expect("failed to read stdout");
+                assert_eq!(want, got, "{} stdout, args: {:?}", name, &args);
+            }
+
+            fs::write(&index_path, "4,5\n").unwrap();
+            let args = vec![
+                "-n",
+                "--continuous",
+                index_path.to_str().unwrap(),
+                t1_path.to_str().unwrap(),
+                t2_path.to_str().unwrap(),
+            ];
+            let output = Command::new(bin)
+                .args(args.clone())
+                .output()
+                .expect("failed to run process");
+            assert!(
+                output.status.success(),
+                "{} status, args: {:?}",
+                name,
+                &args
+            );
+            assert_eq!(
+                b"b1\nb2\n".to_vec(),
+                output.stdout,
+                "{} stdout, args: {:?}",
+                name,
+                &args
+            );
+
+            eprintln!("ok");
+        }
+
+        {
+            let name = "e2e_files_quiet";
+            eprint!("test {} ... ", name);
+
+            for (args, index, target, want) in [
+                (vec!["-n", "--quiet"], "2\n", "l1\nl2\n", Some(0)),
+                (vec!["-n", "-q"], "3\n", "l1\nl2\n", Some(1)),
+                (
+                    vec!["--index-regex", "^a", "-q"],
+                    "b\na\n",
+                    "l1\nl2\n",
+                    Some(0),
+                ),
+                (
+                    vec!["--index-regex", "^a", "-q"],
+                    "b\nc\n",
+                    "l1\nl2\n",
+                    Some(1),
+                ),
+                // stops at the first selected line before the broken INDEX line
+                (vec!["-n", "-q"], "1\nx\n", "l1\nl2\n", Some(0)),
+                (vec!["-n"], "1\nx\n", "l1\nl2\n", Some(2)),
+            ] {
+                let output = e2e_files_output!(name, tmp_dir, bin, args, index, target);
+                assert_eq!(
+                    want,
+                    output.status.code(),
+                    "{} status, args: {:?}",
+                    name,
+                    &args
+                );
+                if args.contains(&"-q") || args.contains(&"--quiet") {
+                    assert!(
+                        output.stdout.is_empty(),
+                        "{} stdout, args: {:?}",
+                        name,
+                        &args
+                    );
+                }
+            }
+
+            eprintln!("ok");
+        }
+
+        {
+            let target = format!("l1\n{}\nl3\n", "x".repeat(1 << 20));
+            test_e2e_files_fail!(
+                "e2e_files_max_line_bytes",
+                tmp_dir,
+                bin,
+                ["--index-line-number", "--max-line-bytes", "16"],
+                ",\n",
+                target,
+                "l1\n",
+                "Target|target=2|index=1|line exceeds 16 bytes"
+            );
+        }
+        test_e2e_files!(
+            "e2e_files_max_line_bytes_within",
+            tmp_dir,
+            bin,
+            ["--index-line-number", "--max-line-bytes", "2"],
+            ",\n",
+            "l1\nl2\n",
+            "l1\nl2\n"
         );
+
         test_e2e_files!(
-            "e2e_files_number",
+            "e2e_files_number_group_separator",
             tmp_dir,
             bin,
-            vec!["--index-line-number", "--index-invert-match"],
-            "1\n3,4\n",
-            "l1\nl2\nl3\nl4\nl5\n",
-            "l2\nl5\n"
+            ["--index-line-number", "--group-separator"],
+            "1\n3,4\n6\n",
+            "l1\nl2\nl3\nl4\nl5\nl6",
+            "l1\n--\nl3\nl4\n--\nl6"
+        );
+        test_e2e_files!(
+            "e2e_files_number_group_separator_string",
+            tmp_dir,
+            bin,
+            [
+                "--index-line-number",
+                "--group-separator=##",
+                "--end-marker",
+                "END"
+            ],
+            "1\n3\n",
+            "l1\nl2\nl3",
+            "l1\n##\nl3\nEND\n"
+        );
+        test_e2e_files!(
+            "e2e_files_number_group_separator_consecutive",
+            tmp_dir,
+            bin,
+            ["--index-line-number", "--group-separator"],
+            "1,2\n",
+            "l1\nl2\nl3\n",
+            "l1\nl2\n"
         );
 
+        test_e2e_files!(
+            "e2e_files_number_exclude_regex",
+            tmp_dir,
+            bin,
+            ["--index-line-number", "--exclude-regex", "skip"],
+            "2,5\n",
+            "l1\nl2 skip\nl3\nl4 skip\nl5\nl6\n",
+            "l3\nl5\n"
+        );
+        test_e2e_files!(
+            "e2e_files_re_exclude_regex",
+            tmp_dir,
+            bin,
+            ["--index-regex", "^a", "--exclude-regex", "^l[12]$"],
+            "a\na\nb\na\n",
+            "l1\nl2\nl3\nl4\n",
+            "l4\n"
+        );
+        test_e2e_files_fail!(
+            "e2e_files_number_exclude_regex_all",
+            tmp_dir,
+            bin,
+            ["--index-line-number", "--exclude-regex", "l"],
+            "1,2\n",
+            "l1\nl2\n",
+            "",
+            ""
+        );
+
+        test_e2e_files!(
+            "e2e_files_number_keep_regex",
+            tmp_dir,
+            bin,
+            ["--index-line-number", "--keep-regex", "keep"],
+            "2,5\n",
+            "l1 keep\nl2\nl3 keep\nl4\nl5 keep\nl6 keep\n",
+            "l3 keep\nl5 keep\n"
+        );
+        test_e2e_files!(
+            "e2e_files_number_keep_regex_exclude_regex",
+            tmp_dir,
+            bin,
+            [
+                "--index-line-number",
+                "--keep-regex",
+                "keep",
+                "--exclude-regex",
+                "5"
+            ],
+            "2,5\n",
+            "l1 keep\nl2\nl3 keep\nl4\nl5 keep\nl6 keep\n",
+            "l3 keep\n"
+        );
+
+        test_e2e!(
+            "e2e_split_marker",
+            tmp_dir,
+            bin,
+            ["--index-line-number", "--split-marker", "---"],
+            "2\n# comment\n---\nl1\nl2\n---\nl3\n",
+            "",
+            "l2\n"
+        );
+        {
+            let name = "e2e_split_marker_stdin";
+            eprint!("test {} ... ", name);
+
+            for (input, want_status, want) in [
+                ("b\na\n===\nl1\nl2\n", Some(0), "l2\n"),
+                ("b\na\n", Some(2), ""),
+            ] {
+                let mut process = Command::new(bin)
+                    .args(["--index-regex", "^a", "--split-marker", "==="])
+                    .stdin(Stdio::piped())
+                    .stdout(Stdio::piped())
+                    .stderr(Stdio::piped())
+                    .spawn()
+                    .expect("failed to spawn process");
+                if let Some(ref mut stdin) = process.stdin {
+                    stdin
+                        .write_all(input.as_bytes())
+                        .expect("failed to write data to stdin");
+                }
+                let output = process.wait_with_output().expect("failed to wait process");
+                let err = String::from_utf8(output.stderr).expect("failed to read stderr");
+                assert_eq!(
+                    want_status,
+                    output.status.code(),
+                    "{} status: {}",
+                    name,
+                    err
+                );
+                assert_eq!(want.as_bytes(), output.stdout.as_slice(), "{} stdout", name);
+                if want_status == Some(2) {
+                    assert!(
+                        err.contains("stdin: marker === not found"),
+                        "{} stderr: {}",
+                        name,
+                        err
+                    );
+                }
+            }
+
+            eprintln!("ok");
+        }
+
         tmp_dir.close().unwrap();
     }
 }
@@ -0,0 +1,102 @@
+use memmap2::Mmap;
+use std::fs::File;
+use std::io;
+use std::path::Path;
+
+/// Random access to lines of a memory-mapped file.
+///
+/// Line offsets are indexed lazily, only as far as the requested line.
+pub struct MmapLines {
+    map: Mmap,
+    /// End offsets (exclusive, including newline) of the lines indexed so far.
+    ends: Vec<usize>,
+}
+
+impl MmapLines {
+    pub fn open<P: AsRef<Path>>(path: P) -> io::Result<MmapLines> {
+        let file = File::open(path)?;
+        // the file should not be modified while mapped
+        let map = unsafe { Mmap::map(&file)? };
+        Ok(MmapLines {
+            map,
+            ends: Vec::new(),
+        })
+    }
+
+    /// Index lines until line `n` or the end of the file.
+    fn scan(&mut self, n: usize) {
+        let mut pos = self.ends.last().copied().unwrap_or(0);
+        while self.ends.len() < n && pos < self.map.len() {
+            pos = match self.map[pos..].iter().position(|&b| b == b'\n') {
+                Some(i) => pos + i + 1,
+                None => self.map.len(),
+            };
+            self.ends.push(pos);
+        }
+    }
+
+    /// Line `n` (1-based) including its newline, None if the file has fewer lines.
+    pub fn line(&mut self, n: usize) -> Option<&[u8]> {
+        if n < 1 {
+            return None;
+        }
+        self.scan(n);
+        let end = *self.ends.get(n - 1)?;
+        let start = if n == 1 { 0 } else { self.ends[n - 2] };
+        Some(&self.map[start..end])
+    }
+
+    /// Number of lines of the file.
+    pub fn len(&mut self) -> usize {
+        self.scan(usize::MAX);
+        self.ends.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.map.is_empty()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::fs;
+    use tempfile::TempDir;
+
+    fn new_lines(content: &str) -> (TempDir, MmapLines) {
+        let dir = TempDir::new().unwrap();
+        let path = dir.path().join("target");
+        fs::write(&path, content).unwrap();
+        let lines = MmapLines::open(&path).unwrap();
+        (dir, lines)
+    }
+
+    #[test]
+    fn mmap_lines_descending() {
+        let (_f, mut lines) = new_lines("l1\nl2\nl3");
+        let got: Vec<Option<Vec<u8>>> = (0..=4)
+            .rev()
+            .map(|n| lines.line(n).map(|x| x.to_vec()))
+            .collect();
+        assert_eq!(
+            vec![
+                None,
+                Some(b"l3".to_vec()),
+                Some(b"l2\n".to_vec()),
+                Some(b"l1\n".to_vec()),
+                None
+            ],
+            got
+        );
+        assert_eq!(3, lines.len());
+    }
+
+    #[test]
+    fn mmap_lines_lazy() {
+        let (_f, mut lines) = new_lines("l1\nl2\nl3\n");
+        assert_eq!(Some("l2\n".as_bytes()), lines.line(2));
+        assert_eq!(2, lines.ends.len());
+        assert_eq!(None, lines.line(4));
+        assert_eq!(3, lines.len());
+    }
+}
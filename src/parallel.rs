@@ -0,0 +1,443 @@
+use crate::index::Type;
+use crate::select::{io_error, read, SelectConfig, SelectError};
+use crate::str::rstrip_delimiter;
+use rayon::prelude::*;
+use std::collections::VecDeque;
+use std::io::{self, BufRead};
+
+/// Select TARGET lines by the regular expression index like [crate::select::Select],
+/// matching chunks of INDEX lines in parallel.
+///
+/// Selected lines are yielded in the order of TARGET.
+/// The selection is the same as [crate::select::Select] only if [ParSelect::supports] the configuration.
+pub struct ParSelect<T, I>
+where
+    T: BufRead,
+    I: BufRead,
+{
+    config: SelectConfig,
+    target_stream: T,
+    index_stream: I,
+    /// Number of line pairs evaluated at once.
+    chunk_size: usize,
+    /// Selected lines not yielded yet.
+    selected: VecDeque<Selected>,
+    /// Error after the selected lines.
+    error: Option<SelectError>,
+    /// Numbers of lines read from TARGET and INDEX so far.
+    target_read: u64,
+    index_read: u32,
+    /// Number of bytes read from TARGET so far.
+    target_bytes: u64,
+    /// TARGET line most recently yielded.
+    current: Selected,
+    /// End of INDEX or TARGET.
+    eof: bool,
+}
+
+/// TARGET line with the position of the pair.
+#[derive(Default)]
+struct Selected {
+    line: String,
+    /// Line number of TARGET.
+    target_line: u64,
+    /// Byte offset in TARGET.
+    target_line_offset: u64,
+    /// Number of lines read from INDEX.
+    index_line: u32,
+    /// INDEX line without newline, None after the end of INDEX.
+    index_content: Option<String>,
+}
+
+impl<T, I> ParSelect<T, I>
+where
+    T: BufRead,
+    I: BufRead,
+{
+    pub fn new(target_stream: T, index_stream: I, config: SelectConfig) -> ParSelect<T, I> {
+        ParSelect {
+            config,
+            target_stream,
+            index_stream,
+            chunk_size: 1024,
+            selected: VecDeque::new(),
+            error: None,
+            target_read: 0,
+            index_read: 0,
+            target_bytes: 0,
+            current: Selected::default(),
+            eof: false,
+        }
+    }
+
+    /// Whether the configuration selects lines by the regular expression index
+    /// without options depending on the preceding lines.
+    pub fn supports(config: &SelectConfig) -> bool {
+        matches!(
+            config.index_type,
+            Some(Type::Re(_) | Type::ReAny(_) | Type::ReAll(_))
+        ) && !config.from_match
+            && config.block_end.is_none()
+            && !config.match_target
+            && !config.skip_blank_target
+            && !config.cycle_index
+    }
+
+    /// Set the number of line pairs evaluated at once.
+    pub fn chunk_size(mut self, v: usize) -> Self {
+        self.chunk_size = v.max(1);
+        self
+    }
+
+    /// Same as [crate::select::Select::target_line].
+    pub fn target_line(&self) -> u64 {
+        self.current.target_line
+    }
+
+    /// Same as [crate::select::Select::target_line_offset].
+    pub fn target_line_offset(&self) -> u64 {
+        self.current.target_line_offset
+    }
+
+    /// Same as [crate::select::Select::index_line].
+    pub fn index_line(&self) -> u64 {
+        self.current.index_line.into()
+    }
+
+    /// Same as [crate::select::Select::index_content].
+    pub fn index_content(&self) -> &str {
+        self.current.index_content.as_deref().unwrap_or_default()
+    }
+
+    /// Same as [crate::select::Select::skip_rest].
+    pub fn skip_rest(&mut self) -> Result<(), SelectError> {
+        self.selected.clear();
+        self.eof = true;
+        loop {
+            let mut line = String::new();
+            match self.read_target(&mut line) {
+                Err(x) => return Err(x),
+                Ok(0) => break,
+                Ok(_) => {}
+            }
+        }
+        self.finish();
+        Ok(())
+    }
+
+    /// Read the next TARGET line, counting it.
+    fn read_target(&mut self, buf: &mut String) -> Result<usize, SelectError> {
+        let n = read(
+            &mut self.target_stream,
+            self.config.delimiter,
+            self.config.max_line_bytes,
+            buf,
+        )
+        .map_err(|x| {
+            io_error(
+                "Target",
+                self.target_read.saturating_add(1),
+                self.index_read,
+                x,
+            )
+        })?;
+        if n > 0 {
+            self.target_read = self.target_read.saturating_add(1);
+            self.target_bytes += n as u64;
+        }
+        Ok(n)
+    }
+
+    /// Read the next INDEX line without delimiter, None at the end of INDEX.
+    fn read_index(&mut self) -> Result<Option<String>, SelectError> {
+        let mut line = String::new();
+        let n = read(
+            &mut self.index_stream,
+            self.config.delimiter,
+            self.config.max_line_bytes,
+            &mut line,
+        )
+        .map_err(|x| io_error("Re", self.target_read, self.index_read.saturating_add(1), x))?;
+        if n == 0 {
+            return Ok(None);
+        }
+        self.index_read = self.index_read.saturating_add(1);
+        rstrip_delimiter(&mut line, self.config.delimiter);
+        Ok(Some(line))
+    }
+
+    /// Read the next chunk of line pairs, stopping at the end of TARGET or INDEX or an error.
+    fn read_chunk(&mut self) -> Result<Vec<Selected>, (Vec<Selected>, SelectError)> {
+        let mut pairs = Vec::with_capacity(self.chunk_size);
+        while pairs.len() < self.chunk_size {
+            let offset = self.target_bytes;
+            let mut line = String::new();
+            match self.read_target(&mut line) {
+                Err(x) => return Err((pairs, x)),
+                Ok(0) => {
+                    self.eof = true;
+                    break;
+                }
+                Ok(_) => {}
+            }
+            let index_content = match self.read_index() {
+                Err(x) => return Err((pairs, x)),
+                // invert end of index, accept all lines
+                Ok(None) if self.config.invert_regex && self.config.accept_after_index => None,
+                // ignore lines in the index file that exceed the number of lines in the target file
+                Ok(None) => {
+                    self.eof = true;
+                    break;
+                }
+                Ok(x) => x,
+            };
+            pairs.push(Selected {
+                line,
+                target_line: self.target_read,
+                target_line_offset: offset,
+                index_line: self.index_read,
+                index_content,
+            });
+        }
+        Ok(pairs)
+    }
+
+    fn select_chunk(&mut self) {
+        let (pairs, mut error) = match self.read_chunk() {
+            Ok(x) => (x, None),
+            Err((x, e)) => {
+                self.eof = true;
+                (x, Some(e))
+            }
+        };
+        let config = &self.config;
+        let matched: Vec<io::Result<bool>> = pairs
+            .par_iter()
+            .map(|x| match &x.index_content {
+                Some(line) => config.match_index(line),
+                None => Ok(!config.invert_regex),
+            })
+            .collect();
+        for (pair, m) in pairs.into_iter().zip(matched) {
+            match m {
+                Err(x) => {
+                    // lines after the error are not selected
+                    error = Some(io_error("Re", pair.target_line, pair.index_line, x));
+                    self.eof = true;
+                    break;
+                }
+                Ok(m) if m != config.invert_regex => self.selected.push_back(pair),
+                Ok(_) => {}
+            }
+        }
+        self.error = error;
+    }
+
+    /// Report all lines read after the end.
+    fn finish(&mut self) {
+        self.current = Selected {
+            target_line: self.target_read,
+            target_line_offset: self.target_bytes,
+            index_line: self.index_read,
+            ..Selected::default()
+        };
+    }
+}
+
+impl<T, I> Iterator for ParSelect<T, I>
+where
+    T: BufRead,
+    I: BufRead,
+{
+    type Item = Result<String, SelectError>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        while self.selected.is_empty() && !self.eof {
+            self.select_chunk();
+        }
+        match self.selected.pop_front() {
+            Some(mut x) => {
+                let line = std::mem::take(&mut x.line);
+                self.current = x;
+                Some(Ok(line))
+            }
+            None => {
+                self.finish();
+                self.error.take().map(Err)
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::pattern::Regex;
+    use std::io::BufReader;
+
+    macro_rules! test_par_select_same_as_select {
+        ($name:ident, $target:expr, $index:expr, $re:expr, $config:expr, $chunk_size:expr) => {
+            #[test]
+            fn $name() {
+                let re = Regex::new($re).unwrap();
+                let config: fn(SelectConfig) -> SelectConfig = $config;
+                let config = config(SelectConfig::new(Some(Type::Re(re)), false));
+                assert!(ParSelect::<&[u8], &[u8]>::supports(&config));
+                let want: Vec<(String, u64, u64)> = {
+                    let mut s = config.run(
+                        BufReader::new($target.as_bytes()),
+                        BufReader::new($index.as_bytes()),
+                    );
+                    let mut v = Vec::new();
+                    while let Some(x) = s.next() {
+                        v.push((x.unwrap(), s.target_line(), s.target_line_offset()));
+                    }
+                    v.push((String::new(), s.target_line(), s.index_line()));
+                    v
+                };
+                let got: Vec<(String, u64, u64)> = {
+                    let mut s = ParSelect::new(
+                        BufReader::new($target.as_bytes()),
+                        BufReader::new($index.as_bytes()),
+                        config,
+                    )
+                    .chunk_size($chunk_size);
+                    let mut v = Vec::new();
+                    while let Some(x) = s.next() {
+                        v.push((x.unwrap(), s.target_line(), s.target_line_offset()));
+                    }
+                    v.push((String::new(), s.target_line(), s.index_line()));
+                    v
+                };
+                assert_eq!(want, got);
+            }
+        };
+    }
+
+    test_par_select_same_as_select!(
+        par_select_re,
+        "l1\nl2\nl3\nl4\nl5\n",
+        "a\nb\na\n\na\n",
+        "a",
+        |c| c,
+        2
+    );
+    test_par_select_same_as_select!(
+        par_select_re_invert,
+        "l1\nl2\nl3\nl4\nl5\n",
+        "a\nb\na\n\na\n",
+        "a",
+        |c| SelectConfig {
+            invert_regex: true,
+            ..c
+        },
+        2
+    );
+    test_par_select_same_as_select!(
+        par_select_re_invert_range,
+        "l1\nl2\nl3\nl4\nl5\n",
+        "a\nb\na\n\na\n",
+        "a",
+        |c| SelectConfig {
+            invert_range: true,
+            ..c
+        },
+        2
+    );
+    test_par_select_same_as_select!(
+        par_select_re_short_index,
+        "l1\nl2\nl3\nl4\nl5\n",
+        "a\na\n",
+        "a",
+        |c| c,
+        3
+    );
+    test_par_select_same_as_select!(
+        par_select_re_short_index_invert,
+        "l1\nl2\nl3\nl4\nl5\n",
+        "b\na\n",
+        "a",
+        |c| SelectConfig {
+            invert_regex: true,
+            ..c
+        },
+        3
+    );
+    test_par_select_same_as_select!(
+        par_select_re_short_index_invert_not_accept_after_index,
+        "l1\nl2\nl3\nl4\nl5\n",
+        "b\na\n",
+        "a",
+        |c| SelectConfig {
+            invert_regex: true,
+            accept_after_index: false,
+            ..c
+        },
+        3
+    );
+    test_par_select_same_as_select!(
+        par_select_re_short_target,
+        "l1\nl2",
+        "a\na\na\n",
+        ".+",
+        |c| c,
+        1
+    );
+    test_par_select_same_as_select!(
+        par_select_re_delimiter,
+        "l1\0l2\nx\0l3\0",
+        "a\0b\0a\0",
+        "^[ab]$",
+        |c| SelectConfig {
+            delimiter: b'\0',
+            ..c
+        },
+        2
+    );
+    test_par_select_same_as_select!(
+        par_select_re_index_field,
+        "l1\nl2\nl3\n",
+        "a,b\nb,a\nc\n",
+        "^a$",
+        |c| SelectConfig {
+            index_field: Some(2),
+            index_delimiter: ',',
+            ..c
+        },
+        2
+    );
+
+    #[test]
+    fn par_select_supports() {
+        let re = Type::Re(Regex::new("a").unwrap());
+        let supports = |c: SelectConfig| ParSelect::<&[u8], &[u8]>::supports(&c);
+        assert!(supports(SelectConfig::new(Some(re.clone()), true)));
+        assert!(!supports(SelectConfig::new(Some(Type::Every(2)), false)));
+        assert!(!supports(SelectConfig::new(None, false)));
+        assert!(!supports(SelectConfig {
+            from_match: true,
+            ..SelectConfig::new(Some(re.clone()), false)
+        }));
+        assert!(!supports(SelectConfig {
+            cycle_index: true,
+            ..SelectConfig::new(Some(re), false)
+        }));
+    }
+
+    #[test]
+    fn par_select_max_line_bytes() {
+        let config = SelectConfig {
+            max_line_bytes: Some(2),
+            ..SelectConfig::new(Some(Type::Re(Regex::new("a").unwrap())), false)
+        };
+        let mut s = ParSelect::new("l1\nl2\n".as_bytes(), "a\naaa\n".as_bytes(), config);
+        assert_eq!("l1\n", s.next().unwrap().unwrap());
+        match s.next() {
+            Some(Err(SelectError::Io(x))) => {
+                assert!(x.to_string().starts_with("Re|target=2|index=2|"), "{}", x)
+            }
+            x => panic!("want error got {:?}", x),
+        }
+        assert!(s.next().is_none());
+    }
+}
@@ -0,0 +1,305 @@
+//! Regular expression engine of the index.
+//!
+//! [Regex] is [regex::Regex], or [fancy_regex::Regex] which supports backreferences and lookaround
+//! with the feature `fancy`.
+//! The feature adds the engine without changing the types, the faster [regex] is used otherwise.
+
+use std::fmt;
+use std::ops;
+use std::time::Duration;
+
+/// Common interface of the regular expression engines.
+pub trait Pattern {
+    /// Whether the regular expression matches somewhere in the string.
+    fn matches(&self, s: &str) -> bool;
+    /// Same as [Pattern::matches] but None if matching takes longer than `timeout`.
+    ///
+    /// Only engines that can backtrack catastrophically enforce the timeout.
+    fn matches_within(&self, s: &str, timeout: Duration) -> Option<bool>;
+}
+
+impl Pattern for regex::Regex {
+    fn matches(&self, s: &str) -> bool {
+        self.is_match(s)
+    }
+
+    /// Matching runs in time linear to the string and is never cut off, `timeout` is ignored.
+    fn matches_within(&self, s: &str, _timeout: Duration) -> Option<bool> {
+        Some(self.is_match(s))
+    }
+}
+
+#[cfg(feature = "fancy")]
+impl Pattern for fancy_regex::Regex {
+    /// Matching failures such as exceeding the backtrack limit are regarded as not matching.
+    fn matches(&self, s: &str) -> bool {
+        self.is_match(s).unwrap_or(false)
+    }
+
+    /// Matching runs in a worker thread reused across calls on the same thread.
+    ///
+    /// A match cannot be interrupted, so on timeout the worker is left to finish it in the background
+    /// and the next call starts a new worker.
+    fn matches_within(&self, s: &str, timeout: Duration) -> Option<bool> {
+        worker::matches_within(self, s, timeout)
+    }
+}
+
+#[cfg(feature = "fancy")]
+mod worker {
+    use super::Pattern;
+    use std::cell::RefCell;
+    use std::sync::mpsc::{self, Receiver, Sender};
+    use std::thread;
+    use std::time::Duration;
+
+    type Job = (fancy_regex::Regex, String);
+
+    /// Thread matching the regular expressions sent to it one by one.
+    struct Worker {
+        jobs: Sender<Job>,
+        results: Receiver<bool>,
+    }
+
+    impl Worker {
+        fn spawn() -> Worker {
+            let (jobs, job_rx) = mpsc::channel::<Job>();
+            let (result_tx, results) = mpsc::channel();
+            thread::spawn(move || {
+                // ends when the worker is dropped
+                for (re, s) in job_rx {
+                    if result_tx.send(re.matches(&s)).is_err() {
+                        break;
+                    }
+                }
+            });
+            Worker { jobs, results }
+        }
+
+        fn matches_within(
+            &self,
+            re: &fancy_regex::Regex,
+            s: &str,
+            timeout: Duration,
+        ) -> Option<bool> {
+            self.jobs.send((re.clone(), s.to_string())).ok()?;
+            self.results.recv_timeout(timeout).ok()
+        }
+    }
+
+    thread_local! {
+        static WORKER: RefCell<Option<Worker>> = const { RefCell::new(None) };
+    }
+
+    pub(super) fn matches_within(
+        re: &fancy_regex::Regex,
+        s: &str,
+        timeout: Duration,
+    ) -> Option<bool> {
+        WORKER.with(|w| {
+            let mut w = w.borrow_mut();
+            let got = w
+                .get_or_insert_with(Worker::spawn)
+                .matches_within(re, s, timeout);
+            if got.is_none() {
+                // still busy with the timed out match
+                *w = None;
+            }
+            got
+        })
+    }
+}
+
+/// Regular expression of the index compiled by one of the engines.
+#[derive(Clone)]
+#[non_exhaustive]
+pub enum Regex {
+    Std(regex::Regex),
+    #[cfg(feature = "fancy")]
+    Fancy(fancy_regex::Regex),
+}
+
+impl Regex {
+    /// Compile the pattern, by [fancy_regex] with the feature `fancy`, otherwise by [regex].
+    pub fn new(re: &str) -> Result<Regex, Error> {
+        #[cfg(feature = "fancy")]
+        return Regex::new_fancy(re);
+        #[cfg(not(feature = "fancy"))]
+        Regex::new_std(re)
+    }
+
+    /// Compile the pattern by [regex].
+    pub fn new_std(re: &str) -> Result<Regex, Error> {
+        regex::Regex::new(re).map(Regex::Std).map_err(Error::Std)
+    }
+
+    /// Compile the pattern by [fancy_regex].
+    #[cfg(feature = "fancy")]
+    pub fn new_fancy(re: &str) -> Result<Regex, Error> {
+        fancy_regex::Regex::new(re)
+            .map(Regex::Fancy)
+            .map_err(|x| Error::Fancy(Box::new(x)))
+    }
+
+    /// The original pattern.
+    pub fn as_str(&self) -> &str {
+        match self {
+            Regex::Std(r) => r.as_str(),
+            #[cfg(feature = "fancy")]
+            Regex::Fancy(r) => r.as_str(),
+        }
+    }
+
+    /// Byte ranges of the successive non-overlapping matches in the string.
+    ///
+    /// With [fancy_regex], a matching failure such as exceeding the backtrack limit ends the matches.
+    pub fn find_ranges(&self, s: &str) -> Vec<ops::Range<usize>> {
+        match self {
+            Regex::Std(r) => r.find_iter(s).map(|m| m.start()..m.end()).collect(),
+            #[cfg(feature = "fancy")]
+            Regex::Fancy(r) => r
+                .find_iter(s)
+                .map_while(Result::ok)
+                .map(|m| m.start()..m.end())
+                .collect(),
+        }
+    }
+}
+
+impl From<regex::Regex> for Regex {
+    fn from(r: regex::Regex) -> Self {
+        Regex::Std(r)
+    }
+}
+
+#[cfg(feature = "fancy")]
+impl From<fancy_regex::Regex> for Regex {
+    fn from(r: fancy_regex::Regex) -> Self {
+        Regex::Fancy(r)
+    }
+}
+
+impl Pattern for Regex {
+    fn matches(&self, s: &str) -> bool {
+        match self {
+            Regex::Std(r) => r.matches(s),
+            #[cfg(feature = "fancy")]
+            Regex::Fancy(r) => r.matches(s),
+        }
+    }
+
+    fn matches_within(&self, s: &str, timeout: Duration) -> Option<bool> {
+        match self {
+            Regex::Std(r) => r.matches_within(s, timeout),
+            #[cfg(feature = "fancy")]
+            Regex::Fancy(r) => r.matches_within(s, timeout),
+        }
+    }
+}
+
+/// Same for both engines as [regex::Regex], e.g. `Regex("^a")`.
+impl fmt::Debug for Regex {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_tuple("Regex").field(&self.as_str()).finish()
+    }
+}
+
+/// The original pattern.
+impl fmt::Display for Regex {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}", self.as_str())
+    }
+}
+
+/// Failure to compile a regular expression.
+#[derive(Debug)]
+#[non_exhaustive]
+pub enum Error {
+    Std(regex::Error),
+    #[cfg(feature = "fancy")]
+    Fancy(Box<fancy_regex::Error>),
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Error::Std(x) => write!(f, "{}", x),
+            #[cfg(feature = "fancy")]
+            Error::Fancy(x) => write!(f, "{}", x),
+        }
+    }
+}
+
+impl std::error::Error for Error {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            Error::Std(x) => Some(x),
+            #[cfg(feature = "fancy")]
+            Error::Fancy(x) => Some(x.as_ref()),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn pattern_matches() {
+        let r = Regex::new("^a+b").unwrap();
+        assert!(r.matches("aab"));
+        assert!(!r.matches("ba"));
+    }
+
+    #[test]
+    fn regex_from_std() {
+        let r = Regex::from(regex::Regex::new("^a").unwrap());
+        assert!(r.matches("ab"));
+        assert_eq!("^a", r.as_str());
+        assert_eq!("^a", r.to_string());
+        assert_eq!(r#"Regex("^a")"#, format!("{:?}", r));
+        assert!(Regex::new_std("(").is_err());
+    }
+
+    #[test]
+    fn regex_find_ranges() {
+        let r = Regex::new("a+").unwrap();
+        assert_eq!(vec![0..2, 4..5], r.find_ranges("aabca"));
+        assert!(r.find_ranges("bc").is_empty());
+    }
+
+    #[cfg(feature = "fancy")]
+    #[test]
+    fn regex_both_engines() {
+        assert!(Regex::new_std(r"^a(?=b)").is_err());
+        let r = Regex::new_fancy(r"^a(?=b)").unwrap();
+        assert!(r.matches("ab"));
+        assert!(!r.matches("ac"));
+        assert!(matches!(Regex::new("^a").unwrap(), Regex::Fancy(_)));
+        assert_eq!(r#"Regex("^a(?=b)")"#, format!("{:?}", r));
+    }
+
+    #[test]
+    fn pattern_matches_within() {
+        let r = Regex::new_std("^a+b").unwrap();
+        assert_eq!(Some(true), r.matches_within("aab", Duration::from_secs(10)));
+        assert_eq!(Some(false), r.matches_within("ba", Duration::from_secs(10)));
+        assert_eq!(Some(true), r.matches_within("aab", Duration::ZERO));
+    }
+
+    #[cfg(feature = "fancy")]
+    #[test]
+    fn pattern_matches_within_fancy() {
+        // the backreference keeps the repetition in the backtracking engine,
+        // which tries every split of the a's into runs of 1 and 2 before failing
+        let r: Regex = fancy_regex::RegexBuilder::new(r"^(?:(a)\1?)+$")
+            .backtrack_limit(usize::MAX)
+            .build()
+            .unwrap()
+            .into();
+        let long = format!("{}c", "a".repeat(100));
+        assert_eq!(None, r.matches_within(&long, Duration::from_millis(100)));
+        assert_eq!(Some(true), r.matches_within("aaa", Duration::from_secs(10)));
+        assert_eq!(Some(false), r.matches_within("ab", Duration::from_secs(10)));
+    }
+}
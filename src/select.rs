@@ -1,18 +1,66 @@
+use crate::channel::ChannelReader;
 use crate::index::Type;
-use crate::lineparse::range;
-use crate::str::rstrip;
-use log::debug;
+use crate::lineparse::{range, range_shifted, range_zero_based, Range};
+use crate::pattern::{Pattern, Regex};
+use crate::str::{read_record, read_record_limited, rstrip_delimiter, strip_delimiter};
+use log::{debug, warn};
 use std::cmp::PartialEq;
-use std::io::BufRead;
+use std::fmt;
+use std::io::{self, BufRead};
 use std::iter::Iterator;
+use std::mem;
+use std::sync::mpsc::Receiver;
+use std::time::Duration;
 use thiserror;
 
-#[derive(Debug, thiserror::Error, PartialEq)]
+/// Failure of selection.
+///
+/// The Display format is stable: `IO (<io error>)` and `Parse (<parse error>)`,
+/// see [ParseError] for the latter.
+/// Io errors convert into [SelectError::Io] by `?`
+/// and are returned by [std::error::Error::source].
+#[derive(Debug, thiserror::Error)]
 pub enum SelectError {
+    /// Failure to read TARGET or INDEX.
     #[error("IO ({0})")]
-    Io(String),
+    Io(#[from] io::Error),
+    /// Failure to parse a line of INDEX, without source.
     #[error("Parse ({0})")]
-    Parse(String),
+    Parse(ParseError),
+}
+
+/// Io errors are equal when their kinds are equal.
+impl PartialEq for SelectError {
+    fn eq(&self, other: &Self) -> bool {
+        match (self, other) {
+            (SelectError::Io(a), SelectError::Io(b)) => a.kind() == b.kind(),
+            (SelectError::Parse(a), SelectError::Parse(b)) => a == b,
+            _ => false,
+        }
+    }
+}
+
+/// Failure to parse a line of INDEX.
+///
+/// The Display format is stable: `index=<index_line>|line=<line>|<message>`.
+#[derive(Debug, Clone, PartialEq)]
+pub struct ParseError {
+    /// Line number of INDEX.
+    pub index_line: u64,
+    /// Content of the INDEX line.
+    pub line: String,
+    /// Description of the failure.
+    pub message: String,
+}
+
+impl fmt::Display for ParseError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            f,
+            "index={}|line={}|{}",
+            self.index_line, self.line, self.message
+        )
+    }
 }
 
 pub struct Select<T, I>
@@ -20,13 +68,35 @@ where
     T: BufRead,
     I: BufRead,
 {
-    index_type: Option<Type>,
-    invert_match: bool,
+    /// Options set by the builder methods,
+    /// the index type and the inversion by line number change as the index advances.
+    config: SelectConfig,
+    /// Whether the INDEX line most recently read is in a block.
+    in_block: bool,
 
     target_stream: T,
-    target_stream_linum: u32,
+    target_stream_linum: u64,
+    /// Number of bytes read from TARGET so far.
+    target_stream_bytes: u64,
+    /// Byte offset of the TARGET line most recently read.
+    target_line_offset: u64,
     index_stream: I,
     index_stream_linum: u32,
+    /// Line number of the TARGET line most recently in the line number ranges,
+    /// the base of relative ranges.
+    last_selected_linum: u32,
+    /// Whether a line number range has been parsed from INDEX.
+    range_parsed: bool,
+    /// INDEX line most recently read in regex mode, without newline.
+    index_content: String,
+    /// End of INDEX, the rest of TARGET is not selected.
+    end_of_index: bool,
+    /// TARGET line most recently read, reused for the next line unless taken.
+    line: String,
+    /// TARGET line read ahead by [Select::peek_target], not consumed yet.
+    peeked: Option<String>,
+    /// All INDEX lines buffered for [Select::cycle_index], read at the first INDEX line.
+    index_cycle: Option<Vec<String>>,
     /// End of iterator.
     eoi: bool,
 }
@@ -39,36 +109,16 @@ where
     type Item = Result<String, SelectError>;
 
     fn next(&mut self) -> Option<Self::Item> {
-        if self.eoi {
-            return None;
-        }
-
-        self.target_stream_linum += 1;
-        debug!("Target|line={}", self.target_stream_linum);
-        let mut line = String::new();
-        match self.target_stream.read_line(&mut line) {
-            Err(x) => {
+        loop {
+            if self.end_of_index {
                 self.disable();
-                Some(Err(SelectError::Io(x.to_string())))
+                return None;
             }
-            // EOF of target
-            Ok(0) => {
-                self.disable();
-                self.next()
+            match self.read_next()? {
+                Ok(true) => return Some(Ok(mem::take(&mut self.line))),
+                Ok(false) => {}
+                Err(x) => return Some(Err(x)),
             }
-            Ok(_) => match self.select(self.target_stream_linum) {
-                SelectResult::Error(x) => {
-                    self.disable();
-                    Some(Err(x))
-                }
-                // EOF of index
-                SelectResult::EndOfIndex => {
-                    self.disable();
-                    self.next()
-                }
-                SelectResult::Accept => Some(Ok(line)),
-                SelectResult::Deny => self.next(),
-            },
         }
     }
 }
@@ -92,15 +142,460 @@ where
         index_type: Option<Type>,
         invert_match: bool,
     ) -> Select<T, I> {
+        SelectConfig::new(index_type, invert_match).run(target_stream, index_stream)
+    }
+
+    fn with_config(config: SelectConfig, target_stream: T, index_stream: I) -> Select<T, I> {
         Select {
-            index_type,
-            invert_match,
+            config,
+            in_block: false,
             target_stream,
             index_stream,
             target_stream_linum: 0,
+            target_stream_bytes: 0,
+            target_line_offset: 0,
+            end_of_index: false,
+            line: String::new(),
+            peeked: None,
+            index_cycle: None,
             eoi: false,
             index_stream_linum: 0,
+            last_selected_linum: 0,
+            range_parsed: false,
+            index_content: String::new(),
+        }
+    }
+
+    /// Set whether to invert the selection by regular expression,
+    /// overriding `invert_match` of [Select::new].
+    pub fn invert_regex(mut self, v: bool) -> Self {
+        self.config.invert_regex = v;
+        self
+    }
+
+    /// Set whether to invert the selection by line number,
+    /// overriding `invert_match` of [Select::new].
+    pub fn invert_range(mut self, v: bool) -> Self {
+        self.config.invert_range = v;
+        self
+    }
+
+    /// Set whether line numbers of the index start from 0 instead of 1.
+    pub fn zero_based(mut self, v: bool) -> Self {
+        self.config.zero_based = v;
+        self
+    }
+
+    /// Set whether an interval whose start is greater than its end is a parse error
+    /// instead of selecting nothing.
+    pub fn strict_range(mut self, v: bool) -> Self {
+        self.config.strict_range = v;
+        self
+    }
+
+    /// Set whether to select all TARGET lines after the line number index is exhausted,
+    /// or to deny them when the selection by line number is inverted.
+    pub fn rest(mut self, v: bool) -> Self {
+        self.config.rest = v;
+        self
+    }
+
+    /// Set whether to accept the TARGET lines after the end of INDEX when the selection is inverted,
+    /// true by default.
+    ///
+    /// Inverted selection accepts the lines INDEX does not select, including those after the end of INDEX.
+    /// When false, only the lines paired with INDEX lines are judged and the selection stops at the end of INDEX.
+    pub fn accept_after_index(mut self, v: bool) -> Self {
+        self.config.accept_after_index = v;
+        self
+    }
+
+    /// Set the number added to the line numbers of the line number index after parsing,
+    /// shifted line numbers less than 1 are errors.
+    pub fn index_base(mut self, v: i64) -> Self {
+        self.config.index_base = v;
+        self
+    }
+
+    /// Set whether to select all TARGET lines from the first line selected by regular expression,
+    /// INDEX is not read after that.
+    pub fn from_match(mut self, v: bool) -> Self {
+        self.config.from_match = v;
+        self
+    }
+
+    /// Set the regular expression of the end of blocks, like awk's /start/,/end/.
+    ///
+    /// A block starts at an INDEX line matching the regular expression index
+    /// and ends at the next INDEX line matching `v`, both inclusive, and lines in blocks are selected.
+    /// The line starting a block can end it too.
+    /// A block not ended continues to the end of INDEX.
+    pub fn block_end(mut self, v: Option<Regex>) -> Self {
+        self.config.block_end = v;
+        self
+    }
+
+    /// Set whether to match the index type against TARGET lines themselves,
+    /// INDEX is not read.
+    pub fn match_target(mut self, v: bool) -> Self {
+        self.config.match_target = v;
+        self
+    }
+
+    /// Set the ASCII byte terminating lines instead of newline.
+    pub fn delimiter(mut self, v: u8) -> Self {
+        self.config.delimiter = v;
+        self
+    }
+
+    /// Set the maximum bytes of a line of TARGET and INDEX without its terminator,
+    /// a longer line is an error instead of being read into memory.
+    pub fn max_line_bytes(mut self, v: Option<usize>) -> Self {
+        self.config.max_line_bytes = v;
+        self
+    }
+
+    /// Set the 1-based field of INDEX lines to match the regular expression index against
+    /// instead of the whole line, a missing field is empty.
+    pub fn index_field(mut self, v: Option<usize>) -> Self {
+        self.config.index_field = v;
+        self
+    }
+
+    /// Set the separator of fields of INDEX lines, tab by default.
+    pub fn index_delimiter(mut self, v: char) -> Self {
+        self.config.index_delimiter = v;
+        self
+    }
+
+    /// Emit a warning listing the line number ranges of INDEX never reached because TARGET ended,
+    /// the rest of INDEX is read to find them.
+    pub fn warn_overshoot(mut self, v: bool) -> Self {
+        self.config.warn_overshoot = v;
+        self
+    }
+
+    /// Set whether to skip blank TARGET lines.
+    ///
+    /// Skipped lines are never selected and do not count toward line numbers,
+    /// so line 2 of the index is the second non-blank line of TARGET.
+    /// In regex mode they are not paired with INDEX lines.
+    pub fn skip_blank_target(mut self, v: bool) -> Self {
+        self.config.skip_blank_target = v;
+        self
+    }
+
+    /// Set the time limit of matching an INDEX line against the regular expression index,
+    /// exceeding it is an io error of [io::ErrorKind::TimedOut].
+    pub fn regex_timeout(mut self, v: Option<Duration>) -> Self {
+        self.config.regex_timeout = v;
+        self
+    }
+
+    /// Set whether to wrap back to the first INDEX line at the end of INDEX in regex mode,
+    /// so that the selection pattern of INDEX repeats over a longer TARGET.
+    ///
+    /// INDEX is read into memory, and [Select::index_line] is the line number in INDEX being used.
+    pub fn cycle_index(mut self, v: bool) -> Self {
+        self.config.cycle_index = v;
+        self
+    }
+
+    /// Number of lines read from TARGET so far,
+    /// i.e. the line number of the TARGET line most recently yielded.
+    pub fn target_line(&self) -> u64 {
+        self.target_stream_linum
+    }
+
+    /// Number of bytes read from TARGET so far, including line terminators.
+    pub fn target_bytes(&self) -> u64 {
+        self.target_stream_bytes
+    }
+
+    /// Byte offset in TARGET of the start of the TARGET line most recently yielded.
+    pub fn target_line_offset(&self) -> u64 {
+        self.target_line_offset
+    }
+
+    /// Number of lines read from INDEX so far.
+    pub fn index_line(&self) -> u64 {
+        self.index_stream_linum.into()
+    }
+
+    /// Content of the INDEX line paired with the TARGET line most recently yielded,
+    /// only in regex mode.
+    pub fn index_content(&self) -> &str {
+        &self.index_content
+    }
+
+    /// Read the rest of TARGET without selecting,
+    /// so that [Select::target_line] becomes the number of all lines of TARGET.
+    pub fn skip_rest(&mut self) -> Result<(), SelectError> {
+        loop {
+            match self.read_target() {
+                Err(x) => {
+                    self.disable();
+                    return Err(io_error(
+                        "Target",
+                        self.target_stream_linum.saturating_add(1),
+                        self.index_stream_linum,
+                        x,
+                    ));
+                }
+                Ok(0) => break,
+                Ok(n) => self.target_stream_bytes += n as u64,
+            }
+            if self.is_skipped() {
+                continue;
+            }
+            self.target_stream_linum = self.target_stream_linum.saturating_add(1);
+        }
+        self.report_overshoot();
+        self.disable();
+        Ok(())
+    }
+
+    /// Convert into an iterator yielding selected lines with their TARGET line numbers.
+    pub fn enumerate_target(self) -> EnumerateTarget<T, I> {
+        EnumerateTarget { select: self }
+    }
+
+    /// Convert into an iterator yielding all TARGET lines with whether they are selected.
+    pub fn tagged(self) -> Tagged<T, I> {
+        Tagged { select: self }
+    }
+
+    /// Read the next TARGET line into `line` and return whether it is selected.
+    fn read_next(&mut self) -> Option<Result<bool, SelectError>> {
+        if self.eoi {
+            return None;
+        }
+
+        match self.read_target() {
+            Err(x) => {
+                self.disable();
+                Some(Err(io_error(
+                    "Target",
+                    self.target_stream_linum.saturating_add(1),
+                    self.index_stream_linum,
+                    x,
+                )))
+            }
+            // EOF of target
+            Ok(0) => {
+                self.report_overshoot();
+                self.disable();
+                None
+            }
+            Ok(n) => {
+                self.target_line_offset = self.target_stream_bytes;
+                self.target_stream_bytes += n as u64;
+                if self.is_skipped() {
+                    return Some(Ok(false));
+                }
+                self.target_stream_linum = self.target_stream_linum.saturating_add(1);
+                debug!("Target|line={}", self.target_stream_linum);
+                let result = if self.end_of_index {
+                    SelectResult::Deny
+                } else if self.config.match_target {
+                    self.select_target(&self.line)
+                } else {
+                    self.select(self.target_stream_linum)
+                };
+                match result {
+                    SelectResult::Error(x) => {
+                        self.disable();
+                        Some(Err(x))
+                    }
+                    // EOF of index
+                    SelectResult::EndOfIndex => {
+                        self.end_of_index = true;
+                        Some(Ok(false))
+                    }
+                    SelectResult::Accept => Some(Ok(true)),
+                    SelectResult::Deny => Some(Ok(false)),
+                }
+            }
+        }
+    }
+
+    /// Read the next TARGET line into `line`, the line read ahead by [Select::peek_target] first.
+    fn read_target(&mut self) -> io::Result<usize> {
+        if let Some(x) = self.peeked.take() {
+            self.line = x;
+            return Ok(self.line.len());
+        }
+        // denied lines leave their buffer to the next line
+        self.line.clear();
+        read(
+            &mut self.target_stream,
+            self.config.delimiter,
+            self.config.max_line_bytes,
+            &mut self.line,
+        )
+    }
+
+    /// Whether the TARGET line in `line` is skipped, see [Select::skip_blank_target].
+    fn is_skipped(&self) -> bool {
+        if !self.config.skip_blank_target {
+            return false;
+        }
+        strip_delimiter(&self.line, self.config.delimiter).is_empty()
+    }
+
+    /// Read the next TARGET line ahead without consuming it, None at the end of TARGET.
+    ///
+    /// The line counters advance when the line is read after this.
+    #[allow(dead_code)] // for modes looking ahead at TARGET
+    fn peek_target(&mut self) -> io::Result<Option<&str>> {
+        if self.peeked.is_none() {
+            let mut line = String::new();
+            if read(
+                &mut self.target_stream,
+                self.config.delimiter,
+                self.config.max_line_bytes,
+                &mut line,
+            )? == 0
+            {
+                return Ok(None);
+            }
+            self.peeked = Some(line);
+        }
+        Ok(self.peeked.as_deref())
+    }
+
+    /// Warn of the line number ranges starting after the end of TARGET, see [Select::warn_overshoot].
+    fn report_overshoot(&mut self) {
+        if !self.config.warn_overshoot || self.eoi || self.config.match_target {
+            return;
+        }
+        let linum = self.target_stream_linum;
+        let mut entries = Vec::new();
+        // relative ranges follow the end of the previous range as if TARGET went on
+        let mut base = self.last_selected_linum;
+        match &self.config.index_type {
+            Some(t @ Type::Number(r)) => {
+                if u64::from(t.start()) > linum {
+                    entries.push(format!("{} (index line {})", r, self.index_stream_linum));
+                }
+                if t.end() != u32::MAX {
+                    base = t.end();
+                }
+            }
+            None => {}
+            // not the line number index
+            Some(_) => return,
+        }
+        loop {
+            let mut index_line = String::new();
+            match read(
+                &mut self.index_stream,
+                self.config.delimiter,
+                self.config.max_line_bytes,
+                &mut index_line,
+            ) {
+                Ok(0) | Err(_) => break,
+                Ok(_) => {}
+            }
+            self.index_stream_linum = self.index_stream_linum.saturating_add(1);
+            rstrip_delimiter(&mut index_line, self.config.delimiter);
+            if index_line.is_empty() || index_line.trim_start().starts_with('#') {
+                continue;
+            }
+            if let Ok((_, (_, Some(x)))) = self.parse_range(&index_line) {
+                let x = x.resolve(base);
+                if let Range::Single(e) | Range::Interval(_, e) = x {
+                    if e != u32::MAX {
+                        base = e;
+                    }
+                }
+                entries.push(format!("{} (index line {})", x, self.index_stream_linum));
+            }
+        }
+        if !entries.is_empty() {
+            warn!(
+                "INDEX has ranges beyond the end of TARGET at line {}: {}",
+                linum,
+                entries.join(", ")
+            );
+        }
+    }
+
+    /// Parse a line of the line number index into the range as written and the range shifted by [Select::index_base],
+    /// None if shifted out of line numbers.
+    fn parse_range<'a>(&self, line: &'a str) -> nom::IResult<&'a str, (Range, Option<Range>)> {
+        let (rest, x) = if self.config.zero_based {
+            range_zero_based(line)?
+        } else if self.config.index_base != 0 {
+            // 0 can be shifted to a line number
+            return range_shifted(line, self.config.index_base);
+        } else {
+            range(line)?
+        };
+        let shifted = x.clone().shift(self.config.index_base);
+        Ok((rest, (x, shifted)))
+    }
+
+    /// Read the rest of INDEX and return whether any line of it is a line number range.
+    fn rest_has_ranges(&mut self) -> bool {
+        loop {
+            let mut index_line = String::new();
+            match read(
+                &mut self.index_stream,
+                self.config.delimiter,
+                self.config.max_line_bytes,
+                &mut index_line,
+            ) {
+                Ok(0) | Err(_) => return false,
+                Ok(_) => {}
+            }
+            rstrip_delimiter(&mut index_line, self.config.delimiter);
+            if self.parse_range(&index_line).is_ok() {
+                return true;
+            }
+        }
+    }
+
+    /// Read the next INDEX line in regex mode, from the buffer of all INDEX lines with [Select::cycle_index].
+    fn read_index(&mut self, buf: &mut String) -> io::Result<usize> {
+        if !self.config.cycle_index {
+            return read(
+                &mut self.index_stream,
+                self.config.delimiter,
+                self.config.max_line_bytes,
+                buf,
+            );
+        }
+        if self.index_cycle.is_none() {
+            let mut lines = Vec::new();
+            loop {
+                let mut line = String::new();
+                if read(
+                    &mut self.index_stream,
+                    self.config.delimiter,
+                    self.config.max_line_bytes,
+                    &mut line,
+                )? == 0
+                {
+                    break;
+                }
+                // for the line number of errors
+                self.index_stream_linum = self.index_stream_linum.saturating_add(1);
+                lines.push(line);
+            }
+            self.index_stream_linum = 0;
+            self.index_cycle = Some(lines);
         }
+        let lines = self.index_cycle.as_deref().unwrap_or_default();
+        if lines.is_empty() {
+            return Ok(0);
+        }
+        if self.index_stream_linum as usize >= lines.len() {
+            self.index_stream_linum = 0;
+        }
+        let line = &lines[self.index_stream_linum as usize];
+        buf.push_str(line);
+        Ok(line.len())
     }
 
     /// Disable self as an iterator.
@@ -108,78 +603,430 @@ where
         self.eoi = true;
     }
 
-    fn select(&mut self, linum: u32) -> SelectResult {
-        match &self.index_type {
-            Some(r @ Type::Re(_)) => {
+    fn select_target(&self, line: &str) -> SelectResult {
+        match &self.config.index_type {
+            Some(r)
+                if r.select(
+                    index_linum(self.target_stream_linum),
+                    strip_delimiter(line, self.config.delimiter),
+                ) != self.config.invert_regex =>
+            {
+                SelectResult::Accept
+            }
+            _ => SelectResult::Deny,
+        }
+    }
+
+    fn select(&mut self, linum: u64) -> SelectResult {
+        // loop instead of recursion to keep the stack constant over many skipped INDEX lines
+        loop {
+            if let Some(x) = self.select_step(linum) {
+                return x;
+            }
+        }
+    }
+
+    /// Judge the TARGET line, None if the index has advanced and the line should be judged again.
+    fn select_step(&mut self, linum: u64) -> Option<SelectResult> {
+        Some(match &self.config.index_type {
+            Some(Type::Re(_) | Type::ReAny(_) | Type::ReAll(_)) => {
                 let mut index_line = String::new();
-                self.index_stream_linum += 1;
-                let s = self.index_stream.read_line(&mut index_line);
+                let s = self.read_index(&mut index_line);
+                if matches!(s, Ok(n) if n > 0) {
+                    self.index_stream_linum = self.index_stream_linum.saturating_add(1);
+                }
                 debug!(
                     "Re|target={}|index={}|line={}",
                     linum, self.index_stream_linum, index_line
                 );
-                rstrip(&mut index_line);
-                match s {
-                    Err(x) => SelectResult::Error(SelectError::Io(x.to_string())),
+                rstrip_delimiter(&mut index_line, self.config.delimiter);
+                let result = match s {
+                    Err(x) => SelectResult::Error(io_error(
+                        "Re",
+                        linum,
+                        self.index_stream_linum.saturating_add(1),
+                        x,
+                    )),
                     // invert end of index, accept all lines
-                    Ok(0) if self.invert_match => SelectResult::Accept,
+                    Ok(0) if self.config.invert_regex && self.config.accept_after_index => {
+                        SelectResult::Accept
+                    }
                     // ignore lines in the index file that exceed the number of lines in the target file
                     Ok(0) => SelectResult::EndOfIndex,
-                    Ok(_) if r.select(0, &index_line) != self.invert_match => SelectResult::Accept,
-                    Ok(_) => SelectResult::Deny,
+                    Ok(_) => match self.config.match_index(&index_line) {
+                        Err(x) => {
+                            SelectResult::Error(io_error("Re", linum, self.index_stream_linum, x))
+                        }
+                        Ok(matched) if self.config.block_end.is_some() => {
+                            let in_block = self.in_block || matched;
+                            // the block ends after this line
+                            self.in_block = in_block
+                                && !self.config.block_end.as_ref().is_some_and(|x| {
+                                    x.matches(self.config.index_text(&index_line))
+                                });
+                            if in_block != self.config.invert_regex {
+                                SelectResult::Accept
+                            } else {
+                                SelectResult::Deny
+                            }
+                        }
+                        Ok(matched) if matched != self.config.invert_regex => SelectResult::Accept,
+                        Ok(_) => SelectResult::Deny,
+                    },
+                };
+                self.index_content = index_line;
+                if self.config.from_match && result == SelectResult::Accept {
+                    // select the rest of the target as the line number range of all lines
+                    self.config.index_type =
+                        Some(Type::Number(Range::Interval(u32::MIN, u32::MAX)));
+                    self.config.invert_range = false;
                 }
+                result
             }
+            // INDEX is not read
+            Some(r @ Type::Every(_))
+                if r.select(index_linum(linum), "") != self.config.invert_range =>
+            {
+                SelectResult::Accept
+            }
+            Some(Type::Every(_)) => SelectResult::Deny,
             // since we have passed the specified range, we will find a new expression
-            Some(r @ Type::Number(_)) if r.end() < linum => {
-                self.index_type = None;
-                self.select(linum)
+            Some(r @ Type::Number(_)) if r.end() < index_linum(linum) => {
+                self.config.index_type = None;
+                return None;
             }
-            Some(r @ Type::Number(_)) if r.select(linum, "") != self.invert_match => {
-                SelectResult::Accept
+            Some(r @ Type::Number(_)) => {
+                let linum = index_linum(linum);
+                let selected = r.select(linum, "");
+                if selected {
+                    self.last_selected_linum = linum;
+                }
+                if selected != self.config.invert_range {
+                    SelectResult::Accept
+                } else {
+                    SelectResult::Deny
+                }
             }
-            Some(Type::Number(_)) => SelectResult::Deny,
             None => {
                 let mut index_line = String::new();
-                self.index_stream_linum += 1;
-                let s = self.index_stream.read_line(&mut index_line);
-                rstrip(&mut index_line);
+                let s = read(
+                    &mut self.index_stream,
+                    self.config.delimiter,
+                    self.config.max_line_bytes,
+                    &mut index_line,
+                );
+                if matches!(s, Ok(n) if n > 0) {
+                    self.index_stream_linum = self.index_stream_linum.saturating_add(1);
+                }
+                rstrip_delimiter(&mut index_line, self.config.delimiter);
                 debug!(
                     "Number|target={}|index={}|line={}",
                     linum, self.index_stream_linum, index_line
                 );
                 match s {
-                    Err(x) => SelectResult::Error(SelectError::Io(x.to_string())),
+                    Err(x) => SelectResult::Error(io_error(
+                        "Number",
+                        linum,
+                        self.index_stream_linum.saturating_add(1),
+                        x,
+                    )),
+                    // select the rest of the target after the end of index
+                    Ok(0) if self.config.rest && !self.config.invert_range => SelectResult::Accept,
+                    Ok(0) if self.config.rest => SelectResult::EndOfIndex,
                     // invert end of index, accept all lines
-                    Ok(0) if self.invert_match => SelectResult::Accept,
+                    Ok(0) if self.config.invert_range && self.config.accept_after_index => {
+                        SelectResult::Accept
+                    }
                     // ignore lines in the index file that exceed the number of lines in the target file
                     Ok(0) => SelectResult::EndOfIndex,
                     // ignore empty lines
-                    Ok(_) if index_line.is_empty() => self.select(linum),
-                    Ok(_) => match range(&index_line) {
-                        Err(x) => SelectResult::Error(SelectError::Parse(format!(
-                            "Number|target={}|index={}|line={}|result={}",
-                            linum, self.index_stream_linum, &index_line, x
-                        ))),
-                        Ok((_, x)) => {
-                            debug!(
-                                "Parsed|target={}|index={}|line={}|range={:?}",
-                                linum, self.index_stream_linum, &index_line, x
-                            );
-                            self.index_type = Some(Type::Number(x));
-                            self.select(linum)
+                    Ok(_) if index_line.is_empty() => return None,
+                    // ignore comment lines
+                    Ok(_) if index_line.trim_start().starts_with('#') => return None,
+                    Ok(_) => match self.parse_range(&index_line) {
+                        Err(x) => {
+                            let x = x.to_string();
+                            // the error is fatal, so the rest of INDEX can be consumed
+                            if !self.range_parsed && !self.rest_has_ranges() {
+                                warn!(
+                                    "INDEX has no line number ranges, INDEX and TARGET may be swapped, try --swap-file-role"
+                                );
+                            }
+                            SelectResult::Error(SelectError::Parse(ParseError {
+                                index_line: self.index_stream_linum.into(),
+                                message: format!("Number|target={}|result={}", linum, x),
+                                line: index_line.clone(),
+                            }))
+                        }
+                        Ok((_, (Range::Interval(s, e), _)))
+                            if self.config.strict_range && s > e =>
+                        {
+                            SelectResult::Error(SelectError::Parse(ParseError {
+                                index_line: self.index_stream_linum.into(),
+                                message: format!(
+                                    "Number|target={}|start {} is greater than end {}",
+                                    linum, s, e
+                                ),
+                                line: index_line.clone(),
+                            }))
                         }
+                        Ok((_, (x, shifted))) => match shifted {
+                            None => SelectResult::Error(SelectError::Parse(ParseError {
+                                index_line: self.index_stream_linum.into(),
+                                message: format!(
+                                    "Number|target={}|{} shifted by {} is out of line numbers",
+                                    linum, x, self.config.index_base
+                                ),
+                                line: index_line.clone(),
+                            })),
+                            Some(x) => {
+                                let x = x.resolve(self.last_selected_linum);
+                                self.range_parsed = true;
+                                debug!(
+                                    "Parsed|target={}|index={}|line={}|range={:?}",
+                                    linum, self.index_stream_linum, &index_line, x
+                                );
+                                self.config.index_type = Some(Type::Number(x));
+                                return None;
+                            }
+                        },
                     },
                 }
             }
+        })
+    }
+}
+
+/// Iterator yielding selected lines with their TARGET line numbers.
+///
+/// See [Select::enumerate_target].
+pub struct EnumerateTarget<T, I>
+where
+    T: BufRead,
+    I: BufRead,
+{
+    select: Select<T, I>,
+}
+
+impl<T, I> Iterator for EnumerateTarget<T, I>
+where
+    T: BufRead,
+    I: BufRead,
+{
+    type Item = Result<(u64, String), SelectError>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let line = self.select.next()?;
+        Some(line.map(|x| (self.select.target_line(), x)))
+    }
+}
+
+/// Iterator yielding all TARGET lines, tagged true if selected and false if not.
+///
+/// See [Select::tagged].
+pub struct Tagged<T, I>
+where
+    T: BufRead,
+    I: BufRead,
+{
+    select: Select<T, I>,
+}
+
+impl<T, I> Iterator for Tagged<T, I>
+where
+    T: BufRead,
+    I: BufRead,
+{
+    type Item = Result<(bool, String), SelectError>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let selected = self.select.read_next()?;
+        Some(selected.map(|x| (x, mem::take(&mut self.select.line))))
+    }
+}
+
+impl<I> Select<ChannelReader, I>
+where
+    I: BufRead,
+{
+    /// Create a selector whose TARGET lines are received from a channel.
+    ///
+    /// See [ChannelReader].
+    pub fn from_channel(
+        target: Receiver<String>,
+        index_stream: I,
+        index_type: Option<Type>,
+        invert_match: bool,
+    ) -> Select<ChannelReader, I> {
+        Select::new(
+            ChannelReader::new(target),
+            index_stream,
+            index_type,
+            invert_match,
+        )
+    }
+}
+
+/// Configuration of [Select] apart from the streams,
+/// to run the same configuration over many pairs of streams without rebuilding the index type.
+#[derive(Debug, Clone)]
+pub struct SelectConfig {
+    pub index_type: Option<Type>,
+    /// Invert the selection by regular expression.
+    pub invert_regex: bool,
+    /// Invert the selection by line number.
+    pub invert_range: bool,
+    /// Line numbers of the index start from 0.
+    pub zero_based: bool,
+    /// Reject intervals whose start is greater than their end.
+    pub strict_range: bool,
+    /// Select all TARGET lines after the end of the line number index.
+    pub rest: bool,
+    /// Accept the TARGET lines after the end of INDEX when the selection is inverted.
+    pub accept_after_index: bool,
+    /// Number added to the line numbers of the line number index.
+    pub index_base: i64,
+    /// Select all TARGET lines from the first line selected by regular expression.
+    pub from_match: bool,
+    /// End of blocks of lines, see [Select::block_end].
+    pub block_end: Option<Regex>,
+    /// Match the index type against TARGET lines instead of INDEX lines.
+    pub match_target: bool,
+    /// Terminator of lines of TARGET and INDEX.
+    pub delimiter: u8,
+    /// Maximum bytes of a line of TARGET and INDEX.
+    pub max_line_bytes: Option<usize>,
+    /// 1-based field of INDEX lines to match the regular expression index against.
+    pub index_field: Option<usize>,
+    /// Separator of fields of INDEX lines.
+    pub index_delimiter: char,
+    /// Warn of line number ranges never reached because TARGET ended.
+    pub warn_overshoot: bool,
+    /// Skip blank TARGET lines, see [Select::skip_blank_target].
+    pub skip_blank_target: bool,
+    /// Time limit of matching an INDEX line against the regular expression index.
+    pub regex_timeout: Option<Duration>,
+    /// Wrap back to the first INDEX line at the end of INDEX in regex mode.
+    pub cycle_index: bool,
+}
+
+impl SelectConfig {
+    pub fn new(index_type: Option<Type>, invert_match: bool) -> SelectConfig {
+        SelectConfig {
+            index_type,
+            invert_regex: invert_match,
+            invert_range: invert_match,
+            zero_based: false,
+            strict_range: false,
+            rest: false,
+            accept_after_index: true,
+            index_base: 0,
+            from_match: false,
+            block_end: None,
+            match_target: false,
+            delimiter: b'\n',
+            max_line_bytes: None,
+            index_field: None,
+            index_delimiter: '\t',
+            warn_overshoot: false,
+            skip_blank_target: false,
+            regex_timeout: None,
+            cycle_index: false,
+        }
+    }
+
+    /// Create a selector over the streams with a copy of this configuration.
+    pub fn run<T, I>(&self, target_stream: T, index_stream: I) -> Select<T, I>
+    where
+        T: BufRead,
+        I: BufRead,
+    {
+        Select::with_config(self.clone(), target_stream, index_stream)
+    }
+
+    /// Part of the INDEX line to match the regular expression index against, see [Select::index_field].
+    pub(crate) fn index_text<'a>(&self, line: &'a str) -> &'a str {
+        match self.index_field {
+            Some(n) => line.split(self.index_delimiter).nth(n - 1).unwrap_or(""),
+            None => line,
+        }
+    }
+
+    /// Match the INDEX line against the regular expression index within [Select::regex_timeout].
+    pub(crate) fn match_index(&self, line: &str) -> io::Result<bool> {
+        let Some(r) = &self.index_type else {
+            return Ok(false);
+        };
+        let line = self.index_text(line);
+        match self.regex_timeout {
+            None => Ok(r.select(0, line)),
+            Some(t) => r.select_within(0, line, t).ok_or_else(|| {
+                io::Error::new(
+                    io::ErrorKind::TimedOut,
+                    format!("regex matching exceeded {}ms", t.as_millis()),
+                )
+            }),
+        }
+    }
+}
+
+/// Io error with the line numbers of TARGET and INDEX being read when it occurred.
+pub(crate) fn io_error(kind: &str, target: u64, index: u32, x: io::Error) -> SelectError {
+    SelectError::Io(io::Error::new(
+        x.kind(),
+        format!("{}|target={}|index={}|{}", kind, target, index, x),
+    ))
+}
+
+/// TARGET line number as the line numbers of the index, lines after [u32::MAX] are regarded as the last one.
+fn index_linum(linum: u64) -> u32 {
+    u32::try_from(linum).unwrap_or(u32::MAX)
+}
+
+/// Read a record, limited to `max_bytes` if any.
+pub(crate) fn read<R: BufRead>(
+    r: &mut R,
+    delimiter: u8,
+    max_bytes: Option<usize>,
+    buf: &mut String,
+) -> io::Result<usize> {
+    match max_bytes {
+        Some(n) => read_record_limited(r, delimiter, buf, n),
+        None => read_record(r, delimiter, buf),
+    }
+}
+
+/// Parse the line number index into ranges without selecting,
+/// e.g. to validate an index file.
+///
+/// Empty lines and comment lines are skipped like [Select] does.
+pub fn parse_index<R: BufRead>(r: R) -> Result<Vec<Range>, SelectError> {
+    let mut ranges = Vec::new();
+    for (i, line) in r.lines().enumerate() {
+        let line = line?;
+        if line.is_empty() || line.trim_start().starts_with('#') {
+            continue;
+        }
+        match range(&line) {
+            Ok((_, x)) => ranges.push(x),
+            Err(x) => {
+                return Err(SelectError::Parse(ParseError {
+                    index_line: i as u64 + 1,
+                    message: format!("Number|result={}", x),
+                    line,
+                }))
+            }
         }
     }
+    Ok(ranges)
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
-    use regex::Regex;
     use std::io::BufReader;
+    use std::sync::mpsc::channel;
+    use std::thread;
 
     macro_rules! test_select_lines {
         ($name:ident, $target:expr, $index:expr, $index_type:expr, $invert_match:expr, $want:expr) => {
@@ -202,6 +1049,14 @@ mod tests {
         false,
         vec!["l1\n", "l3\n"]
     );
+    test_select_lines!(
+        select_lines_number_last_line_without_newline,
+        "l1\nl2",
+        "2\n",
+        None,
+        false,
+        vec!["l2"]
+    );
     test_select_lines!(
         select_lines_number_range,
         "l1\nl2\nl3\nl4\nl5\n",
@@ -227,6 +1082,23 @@ mod tests {
         vec!["l2\n", "l5\n"]
     );
 
+    test_select_lines!(
+        select_lines_number_comment,
+        "l1\nl2\nl3\nl4\nl5\n",
+        "# pick header\n1\n# body\n3,4\n",
+        None,
+        false,
+        vec!["l1\n", "l3\n", "l4\n"]
+    );
+    test_select_lines!(
+        select_lines_number_comment_indented,
+        "l1\nl2\nl3\n",
+        "  # first\n2\n",
+        None,
+        false,
+        vec!["l2\n"]
+    );
+
     test_select_lines!(
         select_lines_re,
         "l1\nl2\nl3\n",
@@ -268,6 +1140,1175 @@ mod tests {
         vec!["l2\n", "l3\n"]
     );
 
+    macro_rules! test_enumerate_target {
+        ($name:ident, $target:expr, $index:expr, $index_type:expr, $invert_match:expr, $want:expr) => {
+            #[test]
+            fn $name() {
+                let target = BufReader::new($target.as_bytes());
+                let index = BufReader::new($index.as_bytes());
+                let s = Select::new(target, index, $index_type, $invert_match);
+                let got: Vec<(u64, String)> = s.enumerate_target().map(|x| x.unwrap()).collect();
+                let want: Vec<(u64, String)> =
+                    $want.into_iter().map(|(n, x)| (n, x.to_string())).collect();
+                assert_eq!(want, got);
+            }
+        };
+    }
+
+    test_enumerate_target!(
+        enumerate_target_number,
+        "l1\nl2\nl3\nl4\nl5\n",
+        "2\n4,5\n",
+        None,
+        false,
+        vec![(2, "l2\n"), (4, "l4\n"), (5, "l5\n")]
+    );
+    test_enumerate_target!(
+        enumerate_target_number_invert,
+        "l1\nl2\nl3\nl4\nl5\n",
+        "2\n4\n",
+        None,
+        true,
+        vec![(1, "l1\n"), (3, "l3\n"), (5, "l5\n")]
+    );
+    test_enumerate_target!(
+        enumerate_target_re,
+        "l1\nl2\nl3\nl4\n",
+        "\n1\n\n1\n",
+        Some(Type::Re(Regex::new(".+").unwrap())),
+        false,
+        vec![(2, "l2\n"), (4, "l4\n")]
+    );
+    test_enumerate_target!(
+        enumerate_target_re_invert,
+        "l1\nl2\nl3\nl4\n",
+        "\n1\n",
+        Some(Type::Re(Regex::new(".+").unwrap())),
+        true,
+        vec![(1, "l1\n"), (3, "l3\n"), (4, "l4\n")]
+    );
+
+    macro_rules! test_tagged {
+        ($name:ident, $target:expr, $index:expr, $index_type:expr, $invert_match:expr, $want:expr) => {
+            #[test]
+            fn $name() {
+                let target = BufReader::new($target.as_bytes());
+                let index = BufReader::new($index.as_bytes());
+                let s = Select::new(target, index, $index_type, $invert_match);
+                let got: Vec<(bool, String)> = s.tagged().map(|x| x.unwrap()).collect();
+                let want: Vec<(bool, String)> =
+                    $want.into_iter().map(|(b, x)| (b, x.to_string())).collect();
+                assert_eq!(want, got);
+            }
+        };
+    }
+
+    test_tagged!(
+        tagged_number,
+        "l1\nl2\nl3\nl4\nl5\n",
+        "2\n4\n",
+        None,
+        false,
+        vec![
+            (false, "l1\n"),
+            (true, "l2\n"),
+            (false, "l3\n"),
+            (true, "l4\n"),
+            (false, "l5\n")
+        ]
+    );
+    test_tagged!(
+        tagged_number_invert,
+        "l1\nl2\nl3\n",
+        "2\n",
+        None,
+        true,
+        vec![(true, "l1\n"), (false, "l2\n"), (true, "l3\n")]
+    );
+    test_tagged!(
+        tagged_re_shorter_index,
+        "l1\nl2\nl3\nl4\n",
+        "\n1\n",
+        Some(Type::Re(Regex::new(".+").unwrap())),
+        false,
+        vec![
+            (false, "l1\n"),
+            (true, "l2\n"),
+            (false, "l3\n"),
+            (false, "l4\n")
+        ]
+    );
+    test_tagged!(
+        tagged_empty_target,
+        "",
+        "1\n",
+        None,
+        false,
+        Vec::<(bool, &str)>::new()
+    );
+
+    macro_rules! test_select_lines_channel {
+        ($name:ident, $target:expr, $index:expr, $index_type:expr, $invert_match:expr, $want:expr) => {
+            #[test]
+            fn $name() {
+                let (tx, rx) = channel();
+                let producer = thread::spawn(move || {
+                    for line in $target {
+                        // the selector may stop receiving before the end
+                        if tx.send(line.to_string()).is_err() {
+                            break;
+                        }
+                    }
+                });
+                let index = BufReader::new($index.as_bytes());
+                let s = Select::from_channel(rx, index, $index_type, $invert_match);
+                let got: Vec<String> = s.map(|x| x.unwrap()).collect();
+                producer.join().unwrap();
+                assert_eq!($want, got);
+            }
+        };
+    }
+
+    test_select_lines_channel!(
+        select_lines_channel_number,
+        ["l1", "l2", "l3", "l4", "l5"],
+        "1\n3,4\n",
+        None,
+        false,
+        vec!["l1\n", "l3\n", "l4\n"]
+    );
+    test_select_lines_channel!(
+        select_lines_channel_re_invert,
+        ["l1", "l2", "l3"],
+        "1\n\n",
+        Some(Type::Re(Regex::new(".+").unwrap())),
+        true,
+        vec!["l2\n", "l3\n"]
+    );
+
+    macro_rules! test_select_lines_invert {
+        ($name:ident, $target:expr, $index:expr, $index_type:expr, $invert_regex:expr, $invert_range:expr, $want:expr) => {
+            #[test]
+            fn $name() {
+                let target = BufReader::new($target.as_bytes());
+                let index = BufReader::new($index.as_bytes());
+                let s = Select::new(target, index, $index_type, false)
+                    .invert_regex($invert_regex)
+                    .invert_range($invert_range);
+                let got: Vec<String> = s.map(|x| x.unwrap()).collect();
+                assert_eq!($want, got);
+            }
+        };
+    }
+
+    test_select_lines_invert!(
+        select_lines_invert_regex_re,
+        "l1\nl2\nl3\n",
+        "1\n\n",
+        Some(Type::Re(Regex::new(".+").unwrap())),
+        true,
+        false,
+        vec!["l2\n", "l3\n"]
+    );
+    test_select_lines_invert!(
+        select_lines_invert_range_re,
+        "l1\nl2\nl3\n",
+        "1\n\n",
+        Some(Type::Re(Regex::new(".+").unwrap())),
+        false,
+        true,
+        vec!["l1\n"]
+    );
+    test_select_lines_invert!(
+        select_lines_invert_regex_number,
+        "l1\nl2\nl3\n",
+        "2\n",
+        None,
+        true,
+        false,
+        vec!["l2\n"]
+    );
+    test_select_lines_invert!(
+        select_lines_invert_range_number,
+        "l1\nl2\nl3\n",
+        "2\n",
+        None,
+        false,
+        true,
+        vec!["l1\n", "l3\n"]
+    );
+
+    #[test]
+    fn select_config_run_twice() {
+        let config = SelectConfig::new(Some(Type::Re(Regex::new("^a").unwrap())), false);
+        let got: Vec<String> = config
+            .run(
+                BufReader::new("l1\nl2\nl3\n".as_bytes()),
+                BufReader::new("a\nb\na\n".as_bytes()),
+            )
+            .map(|x| x.unwrap())
+            .collect();
+        assert_eq!(vec!["l1\n", "l3\n"], got);
+        let got: Vec<String> = config
+            .run(
+                BufReader::new("m1\nm2\n".as_bytes()),
+                BufReader::new("b\na\n".as_bytes()),
+            )
+            .map(|x| x.unwrap())
+            .collect();
+        assert_eq!(vec!["m2\n"], got);
+    }
+
+    #[test]
+    fn select_config_run_number() {
+        let mut config = SelectConfig::new(None, false);
+        config.zero_based = true;
+        for (target, want) in [("l1\nl2\n", vec!["l2\n"]), ("m1\nm2\nm3\n", vec!["m2\n"])] {
+            let got: Vec<String> = config
+                .run(
+                    BufReader::new(target.as_bytes()),
+                    BufReader::new("1\n".as_bytes()),
+                )
+                .map(|x| x.unwrap())
+                .collect();
+            assert_eq!(want, got);
+        }
+    }
+
+    #[test]
+    fn select_config_run_strict_range() {
+        let mut config = SelectConfig::new(None, false);
+        config.strict_range = true;
+        let got: Vec<Result<String, SelectError>> = config
+            .run(
+                BufReader::new("l1\nl2\nl3\n".as_bytes()),
+                BufReader::new("3,1\n".as_bytes()),
+            )
+            .collect();
+        assert_eq!(1, got.len());
+        assert!(matches!(got[0], Err(SelectError::Parse(_))));
+    }
+
+    #[test]
+    fn parse_index_ranges() {
+        let got = parse_index(BufReader::new("1\n\n# comment\n3,5\n".as_bytes())).unwrap();
+        assert_eq!(vec![Range::Single(1), Range::Interval(3, 5)], got);
+    }
+
+    #[test]
+    fn parse_index_error() {
+        let got = parse_index(BufReader::new("1\nx\n3\n".as_bytes()));
+        match got {
+            Err(SelectError::Parse(x)) => {
+                assert_eq!(2, x.index_line);
+                assert_eq!("x", x.line);
+            }
+            x => panic!("unexpected result {:?}", x),
+        }
+    }
+
+    #[test]
+    fn select_error_from_io() {
+        fn fail() -> Result<(), SelectError> {
+            Err(io::Error::new(io::ErrorKind::NotFound, "gone"))?;
+            Ok(())
+        }
+        let got = fail().unwrap_err();
+        assert!(matches!(&got, SelectError::Io(x) if x.kind() == io::ErrorKind::NotFound));
+        assert_eq!("IO (gone)", got.to_string());
+        let source = std::error::Error::source(&got).expect("source of io error");
+        assert_eq!("gone", source.to_string());
+        assert!(source.downcast_ref::<io::Error>().is_some());
+    }
+
+    #[test]
+    fn select_error_parse_display() {
+        let got = SelectError::Parse(ParseError {
+            index_line: 2,
+            line: "x".to_string(),
+            message: "Number|result=bad".to_string(),
+        });
+        assert_eq!("Parse (index=2|line=x|Number|result=bad)", got.to_string());
+        assert!(std::error::Error::source(&got).is_none());
+    }
+
+    test_select_lines!(
+        select_lines_re_empty_index,
+        "l1\nl2\n",
+        "",
+        Some(Type::Re(Regex::new(".+").unwrap())),
+        false,
+        Vec::<String>::new()
+    );
+    test_select_lines!(
+        select_lines_re_empty_index_invert,
+        "l1\nl2\n",
+        "",
+        Some(Type::Re(Regex::new(".+").unwrap())),
+        true,
+        vec!["l1\n", "l2\n"]
+    );
+    test_select_lines!(
+        select_lines_number_empty_index,
+        "l1\nl2\n",
+        "",
+        None,
+        false,
+        Vec::<String>::new()
+    );
+    test_select_lines!(
+        select_lines_number_empty_index_invert,
+        "l1\nl2\n",
+        "",
+        None,
+        true,
+        vec!["l1\n", "l2\n"]
+    );
+    test_select_lines!(
+        select_lines_number_comment_only_index,
+        "l1\nl2\n",
+        "# c\n\n",
+        None,
+        false,
+        Vec::<String>::new()
+    );
+    test_select_lines!(
+        select_lines_number_all,
+        "l1\nl2\nl3\n",
+        ",\n",
+        None,
+        false,
+        vec!["l1\n", "l2\n", "l3\n"]
+    );
+    test_select_lines!(
+        select_lines_number_all_invert,
+        "l1\nl2\nl3\n",
+        ",\n",
+        None,
+        true,
+        Vec::<String>::new()
+    );
+    test_select_lines!(
+        select_lines_every,
+        "l1\nl2\nl3\nl4\nl5\n",
+        "",
+        Some(Type::Every(2)),
+        false,
+        vec!["l2\n", "l4\n"]
+    );
+    test_select_lines!(
+        select_lines_every_invert,
+        "l1\nl2\nl3\nl4\nl5\n",
+        "",
+        Some(Type::Every(2)),
+        true,
+        vec!["l1\n", "l3\n", "l5\n"]
+    );
+
+    #[test]
+    fn select_lines_counters() {
+        let target = BufReader::new("l1\nl2\nl3\nl4\nl5\n".as_bytes());
+        let index = BufReader::new("2\n# c\n4\n".as_bytes());
+        let mut s = Select::new(target, index, None, false);
+        assert_eq!((0, 0), (s.target_line(), s.index_line()));
+        assert_eq!(Some("l2\n".to_string()), s.next().map(|x| x.unwrap()));
+        assert_eq!((2, 1), (s.target_line(), s.index_line()));
+        assert_eq!(Some("l4\n".to_string()), s.next().map(|x| x.unwrap()));
+        assert_eq!((4, 3), (s.target_line(), s.index_line()));
+        assert!(s.next().is_none());
+        assert_eq!((5, 3), (s.target_line(), s.index_line()));
+    }
+
+    #[test]
+    fn select_skip_rest() {
+        let target = BufReader::new("l1\nl2\nl3\nl4\n".as_bytes());
+        let index = BufReader::new("2\n".as_bytes());
+        let mut s = Select::new(target, index, None, false);
+        let got: Vec<String> = s.by_ref().map(|x| x.unwrap()).collect();
+        assert_eq!(vec!["l2\n"], got);
+        assert_eq!(3, s.target_line());
+        s.skip_rest().unwrap();
+        assert_eq!(4, s.target_line());
+        assert!(s.next().is_none());
+    }
+
+    #[test]
+    fn select_lines_counters_re_invert() {
+        let target = BufReader::new("l1\nl2\nl3\n".as_bytes());
+        let index = BufReader::new("\n".as_bytes());
+        let mut s = Select::new(
+            target,
+            index,
+            Some(Type::Re(Regex::new(".+").unwrap())),
+            true,
+        );
+        let got: Vec<String> = s.by_ref().map(|x| x.unwrap()).collect();
+        assert_eq!(vec!["l1\n", "l2\n", "l3\n"], got);
+        assert_eq!((3, 1), (s.target_line(), s.index_line()));
+    }
+
+    #[test]
+    fn select_lines_delimiter_nul() {
+        let target = BufReader::new("l1\0l2\nx\0l3\0".as_bytes());
+        let index = BufReader::new("2\0".as_bytes());
+        let s = Select::new(target, index, None, false).delimiter(b'\0');
+        let got: Vec<String> = s.map(|x| x.unwrap()).collect();
+        assert_eq!(vec!["l2\nx\0"], got);
+    }
+
+    #[test]
+    fn select_lines_delimiter_re() {
+        let target = BufReader::new("l1;l2;l3".as_bytes());
+        let index = BufReader::new("a;;c;".as_bytes());
+        let s = Select::new(
+            target,
+            index,
+            Some(Type::Re(Regex::new(".+").unwrap())),
+            false,
+        )
+        .delimiter(b';');
+        let got: Vec<String> = s.map(|x| x.unwrap()).collect();
+        assert_eq!(vec!["l1;", "l3"], got);
+    }
+
+    #[test]
+    fn select_lines_index_content() {
+        let target = BufReader::new("l1\nl2\nl3\n".as_bytes());
+        let index = BufReader::new("a\n\nc\n".as_bytes());
+        let mut s = Select::new(
+            target,
+            index,
+            Some(Type::Re(Regex::new(".+").unwrap())),
+            false,
+        );
+        assert_eq!(Some("l1\n".to_string()), s.next().map(|x| x.unwrap()));
+        assert_eq!("a", s.index_content());
+        assert_eq!(Some("l3\n".to_string()), s.next().map(|x| x.unwrap()));
+        assert_eq!("c", s.index_content());
+    }
+
+    #[test]
+    fn select_lines_match_target() {
+        let target = BufReader::new("a1\nb2\na3\n".as_bytes());
+        let s = Select::new(
+            target,
+            io::empty(),
+            Some(Type::Re(Regex::new("^a").unwrap())),
+            false,
+        )
+        .match_target(true);
+        let got: Vec<String> = s.map(|x| x.unwrap()).collect();
+        assert_eq!(vec!["a1\n", "a3\n"], got);
+    }
+
+    #[test]
+    fn select_lines_match_target_delimiter() {
+        let target = BufReader::new("a1;b2;a3;".as_bytes());
+        let s = Select::new(
+            target,
+            io::empty(),
+            Some(Type::Re(Regex::new("3$").unwrap())),
+            false,
+        )
+        .match_target(true)
+        .delimiter(b';');
+        let got: Vec<String> = s.map(|x| x.unwrap()).collect();
+        assert_eq!(vec!["a3;"], got);
+    }
+
+    #[test]
+    fn select_lines_number_zero_based() {
+        let target = BufReader::new("l1\nl2\nl3\nl4\n".as_bytes());
+        let index = BufReader::new("0\n2,\n".as_bytes());
+        let s = Select::new(target, index, None, false).zero_based(true);
+        let got: Vec<String> = s.map(|x| x.unwrap()).collect();
+        assert_eq!(vec!["l1\n", "l3\n", "l4\n"], got);
+    }
+
+    #[cfg(feature = "fancy")]
+    #[test]
+    fn select_lines_re_fancy() {
+        let target = BufReader::new("l1\nl2\nl3\nl4\n".as_bytes());
+        let index = BufReader::new("ab\nac\naa\nbb\n".as_bytes());
+        let s = Select::new(
+            target,
+            index,
+            Some(Type::ReAny(vec![
+                Regex::new(r"^a(?=b)").unwrap(),
+                Regex::new(r"^(\w)\1$").unwrap(),
+            ])),
+            false,
+        );
+        let got: Vec<String> = s.map(|x| x.unwrap()).collect();
+        assert_eq!(vec!["l1\n", "l3\n", "l4\n"], got);
+    }
+
+    #[test]
+    fn select_lines_number_warn_overshoot_reads_rest_of_index() {
+        for (warn_overshoot, want_index_line) in [(false, 1), (true, 3)] {
+            let mut s = Select::new(
+                BufReader::new("l1\nl2\nl3\n".as_bytes()),
+                BufReader::new("10\n11\n12\n".as_bytes()),
+                None,
+                false,
+            )
+            .warn_overshoot(warn_overshoot);
+            assert!(s.next().is_none());
+            assert_eq!(3, s.target_line());
+            assert_eq!(want_index_line, s.index_line());
+        }
+    }
+
+    #[test]
+    fn select_lines_number_many_skipped_index_lines() {
+        for skipped in ["\n", "# comment\n", "1\n"] {
+            let index = format!("{}3\n", skipped.repeat(100_000));
+            let s = Select::new(
+                BufReader::new("l1\nl2\nl3\nl4\n".as_bytes()),
+                BufReader::new(index.as_bytes()),
+                None,
+                false,
+            );
+            let want: Vec<&str> = match skipped {
+                "1\n" => vec!["l1\n", "l3\n"],
+                _ => vec!["l3\n"],
+            };
+            let got: Vec<String> = s.map(|x| x.unwrap()).collect();
+            assert_eq!(want, got);
+        }
+    }
+
+    #[test]
+    fn select_lines_many_denied_lines() {
+        let lines = 100_000;
+        let target: String = (1..=lines).map(|i| format!("l{}\n", i)).collect();
+        let want = vec![format!("l{}\n", lines)];
+        for (index, index_type) in [
+            (format!("{}\n", lines), None),
+            (
+                format!("{}x\n", "\n".repeat(lines - 1)),
+                Some(Type::Re(Regex::new(".+").unwrap())),
+            ),
+        ] {
+            let mut s = Select::new(
+                BufReader::new(target.as_bytes()),
+                BufReader::new(index.as_bytes()),
+                index_type,
+                false,
+            );
+            let got: Vec<String> = s.by_ref().map(|x| x.unwrap()).collect();
+            assert_eq!(want, got);
+            assert!(s.next().is_none());
+        }
+    }
+
+    #[test]
+    fn select_lines_target_line_offset() {
+        let mut s = Select::new(
+            BufReader::new("l1\nあ\r\n\nl4\nl5".as_bytes()),
+            BufReader::new("2\n4,\n".as_bytes()),
+            None,
+            false,
+        );
+        let mut got = Vec::new();
+        while let Some(x) = s.next() {
+            got.push((s.target_line_offset(), x.unwrap()));
+        }
+        let want: Vec<(u64, String)> = vec![
+            (3, "あ\r\n".to_string()),
+            (9, "l4\n".to_string()),
+            (12, "l5".to_string()),
+        ];
+        assert_eq!(want, got);
+        assert_eq!(14, s.target_bytes());
+    }
+
+    #[test]
+    fn index_linum_saturates() {
+        assert_eq!(3, index_linum(3));
+        assert_eq!(u32::MAX, index_linum(u64::from(u32::MAX)));
+        assert_eq!(u32::MAX, index_linum(u64::from(u32::MAX) + 1));
+    }
+
+    #[test]
+    fn peek_target() {
+        let mut s = Select::new(
+            BufReader::new("l1\nl2\nl3".as_bytes()),
+            BufReader::new("2,\n".as_bytes()),
+            None,
+            false,
+        );
+        assert_eq!(Some("l1\n"), s.peek_target().unwrap());
+        assert_eq!(Some("l1\n"), s.peek_target().unwrap());
+        assert_eq!(0, s.target_line());
+        assert_eq!(0, s.target_bytes());
+
+        assert_eq!(Some("l2\n".to_string()), s.next().map(|x| x.unwrap()));
+        assert_eq!(2, s.target_line());
+        assert_eq!(6, s.target_bytes());
+        assert_eq!(3, s.target_line_offset());
+
+        assert_eq!(Some("l3"), s.peek_target().unwrap());
+        assert_eq!(2, s.target_line());
+        assert_eq!(Some("l3".to_string()), s.next().map(|x| x.unwrap()));
+        assert_eq!(3, s.target_line());
+        assert_eq!(8, s.target_bytes());
+
+        assert_eq!(None, s.peek_target().unwrap());
+        assert!(s.next().is_none());
+        assert_eq!(3, s.target_line());
+    }
+
+    #[test]
+    fn select_lines_reused_buffer_has_no_stale_content() {
+        let target = "a long denied line\nx\nanother long denied line\ny\nz";
+        for (delimiter, target) in [
+            (b'\n', target.to_string()),
+            (b'\0', target.replace('\n', "\0")),
+        ] {
+            let d = delimiter as char;
+            let (index, index_tagged) = (format!("2{}4,5{}", d, d), format!("2{}", d));
+            let s = Select::new(
+                BufReader::new(target.as_bytes()),
+                BufReader::new(index.as_bytes()),
+                None,
+                false,
+            )
+            .delimiter(delimiter);
+            let got: Vec<String> = s.map(|x| x.unwrap()).collect();
+            assert_eq!(
+                vec![format!("x{}", d), format!("y{}", d), "z".to_string()],
+                got
+            );
+
+            let s = Select::new(
+                BufReader::new(target.as_bytes()),
+                BufReader::new(index_tagged.as_bytes()),
+                None,
+                false,
+            )
+            .delimiter(delimiter);
+            let got: Vec<(bool, String)> = s.tagged().map(|x| x.unwrap()).collect();
+            let want: Vec<(bool, String)> = target
+                .split_inclusive(d)
+                .enumerate()
+                .map(|(i, x)| (i == 1, x.to_string()))
+                .collect();
+            assert_eq!(want, got);
+        }
+    }
+
+    #[test]
+    fn select_lines_re_index_field() {
+        for (delimiter, index, want) in [
+            ('\t', "a\tx\nx\ta\nb\tab\nc\n", vec!["l2\n", "l3\n"]),
+            (',', "a,x\nx,a\nb,b\n", vec!["l2\n"]),
+        ] {
+            let s = Select::new(
+                BufReader::new("l1\nl2\nl3\nl4\n".as_bytes()),
+                BufReader::new(index.as_bytes()),
+                Some(Type::Re(Regex::new("^a").unwrap())),
+                false,
+            )
+            .index_field(Some(2))
+            .index_delimiter(delimiter);
+            let got: Vec<String> = s.map(|x| x.unwrap()).collect();
+            assert_eq!(want, got);
+        }
+    }
+
+    #[test]
+    fn select_lines_re_from_match() {
+        let target = BufReader::new("l1\nl2\nl3\nl4\n".as_bytes());
+        let index = BufReader::new("b\na\nb\n".as_bytes());
+        let s = Select::new(
+            target,
+            index,
+            Some(Type::Re(Regex::new("^a").unwrap())),
+            false,
+        )
+        .from_match(true);
+        let got: Vec<String> = s.map(|x| x.unwrap()).collect();
+        assert_eq!(vec!["l2\n", "l3\n", "l4\n"], got);
+    }
+
+    macro_rules! test_select_lines_block {
+        ($name:ident, $target:expr, $index:expr, $start:expr, $end:expr, $invert:expr, $want:expr) => {
+            #[test]
+            fn $name() {
+                let s = Select::new(
+                    BufReader::new($target.as_bytes()),
+                    BufReader::new($index.as_bytes()),
+                    Some(Type::Re(Regex::new($start).unwrap())),
+                    $invert,
+                )
+                .block_end(Some(Regex::new($end).unwrap()));
+                let got: Vec<String> = s.map(|x| x.unwrap()).collect();
+                let want: Vec<&str> = $want;
+                assert_eq!(want, got);
+            }
+        };
+    }
+
+    test_select_lines_block!(
+        select_lines_block_single,
+        "l1\nl2\nl3\nl4\nl5\n",
+        "x\nstart\ny\nend\nz\n",
+        "start",
+        "end",
+        false,
+        vec!["l2\n", "l3\n", "l4\n"]
+    );
+    test_select_lines_block!(
+        select_lines_block_multiple,
+        "l1\nl2\nl3\nl4\nl5\nl6\n",
+        "start\nend\nx\nstart\nend\nend\n",
+        "start",
+        "end",
+        false,
+        vec!["l1\n", "l2\n", "l4\n", "l5\n"]
+    );
+    test_select_lines_block!(
+        select_lines_block_not_ended,
+        "l1\nl2\nl3\n",
+        "x\nstart\ny\n",
+        "start",
+        "end",
+        false,
+        vec!["l2\n", "l3\n"]
+    );
+    test_select_lines_block!(
+        select_lines_block_same_line,
+        "l1\nl2\nl3\n",
+        "x\nstart end\ny\n",
+        "start",
+        "end",
+        false,
+        vec!["l2\n"]
+    );
+    test_select_lines_block!(
+        select_lines_block_invert,
+        "l1\nl2\nl3\nl4\n",
+        "x\nstart\nend\ny\n",
+        "start",
+        "end",
+        true,
+        vec!["l1\n", "l4\n"]
+    );
+
+    #[test]
+    fn select_lines_number_right_open_to_eof() {
+        for index_type in [None, Some(Type::Number(Range::Interval(5, u32::MAX)))] {
+            let index = if index_type.is_none() { "5,\n" } else { "" };
+            let mut s = Select::new(
+                BufReader::new("l1\nl2\nl3\nl4\nl5\nl6\nl7".as_bytes()),
+                BufReader::new(index.as_bytes()),
+                index_type,
+                false,
+            );
+            let got: Vec<String> = s.by_ref().map(|x| x.unwrap()).collect();
+            assert_eq!(vec!["l5\n", "l6\n", "l7"], got);
+            assert_eq!(7, s.target_line());
+            assert!(s.next().is_none());
+        }
+    }
+
+    test_select_lines!(
+        select_lines_number_right_open_invert,
+        "l1\nl2\nl3\n",
+        "2,\n",
+        None,
+        true,
+        vec!["l1\n"]
+    );
+
+    test_select_lines!(
+        select_lines_number_relative,
+        "l1\nl2\nl3\nl4\nl5\n",
+        "2\n+2\n",
+        None,
+        false,
+        vec!["l2\n", "l4\n"]
+    );
+    test_select_lines!(
+        select_lines_number_relative_first,
+        "l1\nl2\nl3\nl4\nl5\n",
+        "+2\n+1\n",
+        None,
+        false,
+        vec!["l2\n", "l3\n"]
+    );
+    test_select_lines!(
+        select_lines_number_relative_after_interval,
+        "l1\nl2\nl3\nl4\nl5\nl6\n",
+        "1,3\n+2\n",
+        None,
+        false,
+        vec!["l1\n", "l2\n", "l3\n", "l5\n"]
+    );
+    test_select_lines!(
+        select_lines_number_relative_invert,
+        "l1\nl2\nl3\nl4\nl5\n",
+        "2\n+2\n",
+        None,
+        true,
+        vec!["l1\n", "l3\n", "l5\n"]
+    );
+
+    #[test]
+    fn select_lines_number_rest() {
+        let target = BufReader::new("l1\nl2\nl3\nl4\nl5\n".as_bytes());
+        let index = BufReader::new("1\n3\n".as_bytes());
+        let s = Select::new(target, index, None, false).rest(true);
+        let got: Vec<String> = s.map(|x| x.unwrap()).collect();
+        assert_eq!(vec!["l1\n", "l3\n", "l4\n", "l5\n"], got);
+    }
+
+    #[test]
+    fn select_lines_number_rest_invert() {
+        let target = BufReader::new("l1\nl2\nl3\nl4\nl5\n".as_bytes());
+        let index = BufReader::new("1\n3\n".as_bytes());
+        let s = Select::new(target, index, None, true).rest(true);
+        let got: Vec<String> = s.map(|x| x.unwrap()).collect();
+        assert_eq!(vec!["l2\n"], got);
+    }
+
+    #[test]
+    fn select_lines_number_index_base() {
+        for (index, base, want) in [
+            ("0\n2,3\n", 1, vec!["l1\n", "l3\n", "l4\n"]),
+            ("0,1\n4,\n", 1, vec!["l1\n", "l2\n", "l5\n"]),
+            ("3\n5,\n", -2, vec!["l1\n", "l3\n", "l4\n", "l5\n"]),
+            ("1\n+2\n", 1, vec!["l2\n", "l4\n"]),
+            ("0,1\n", 2, vec!["l2\n", "l3\n"]),
+            (",1\n", 2, vec!["l1\n", "l2\n", "l3\n"]),
+        ] {
+            let target = BufReader::new("l1\nl2\nl3\nl4\nl5\n".as_bytes());
+            let s =
+                Select::new(target, BufReader::new(index.as_bytes()), None, false).index_base(base);
+            let got: Vec<String> = s.map(|x| x.unwrap()).collect();
+            assert_eq!(want, got, "{} {}", index, base);
+        }
+    }
+
+    #[test]
+    fn select_lines_number_index_base_out_of_line_numbers() {
+        let target = BufReader::new("l1\nl2\nl3\n".as_bytes());
+        let index = BufReader::new("3\n1\n".as_bytes());
+        let mut s = Select::new(target, index, None, false).index_base(-1);
+        assert_eq!(Some("l2\n".to_string()), s.next().map(|x| x.unwrap()));
+        assert!(matches!(s.next(), Some(Err(SelectError::Parse(_)))));
+        assert!(s.next().is_none());
+    }
+
+    #[test]
+    fn select_lines_accept_after_index() {
+        for (index, index_type, accept_after_index, want) in [
+            ("1\n3\n", None, true, vec!["l2\n", "l4\n", "l5\n"]),
+            ("1\n3\n", None, false, vec!["l2\n"]),
+            (
+                "x\n\nx\n",
+                Some(Type::Re(Regex::new(".+").unwrap())),
+                true,
+                vec!["l2\n", "l4\n", "l5\n"],
+            ),
+            (
+                "x\n\nx\n",
+                Some(Type::Re(Regex::new(".+").unwrap())),
+                false,
+                vec!["l2\n"],
+            ),
+        ] {
+            let target = BufReader::new("l1\nl2\nl3\nl4\nl5\n".as_bytes());
+            let s = Select::new(target, BufReader::new(index.as_bytes()), index_type, true)
+                .accept_after_index(accept_after_index);
+            let got: Vec<String> = s.map(|x| x.unwrap()).collect();
+            assert_eq!(want, got, "{} {}", index, accept_after_index);
+        }
+    }
+
+    test_select_lines!(
+        select_lines_number_start_greater_than_end,
+        "l1\nl2\nl3\nl4\n",
+        "1\n3,2\n4\n",
+        None,
+        false,
+        vec!["l1\n", "l4\n"]
+    );
+
+    #[test]
+    fn select_lines_number_strict_range_error() {
+        let target = BufReader::new("l1\nl2\nl3\n".as_bytes());
+        let index = BufReader::new("1\n3,2\n".as_bytes());
+        let mut s = Select::new(target, index, None, false).strict_range(true);
+        assert_eq!(Some("l1\n".to_string()), s.next().map(|x| x.unwrap()));
+        match s.next() {
+            Some(Err(SelectError::Parse(x))) => {
+                assert_eq!(2, x.index_line);
+                assert_eq!("3,2", x.line);
+            }
+            x => panic!("want parse error got {:?}", x),
+        }
+        assert!(s.next().is_none());
+    }
+
+    #[test]
+    fn select_lines_number_parse_error() {
+        let target = BufReader::new("l1\nl2\nl3\n".as_bytes());
+        let index = BufReader::new("1\n\nx\n".as_bytes());
+        let mut s = Select::new(target, index, None, false);
+        assert_eq!(Some("l1\n".to_string()), s.next().map(|x| x.unwrap()));
+        match s.next() {
+            Some(Err(SelectError::Parse(x))) => {
+                assert_eq!(3, x.index_line);
+                assert_eq!("x", x.line);
+            }
+            x => panic!("want parse error got {:?}", x),
+        }
+        assert!(s.next().is_none());
+    }
+
+    macro_rules! test_select_io_error {
+        ($name:ident, $target:expr, $index:expr, $index_type:expr, $want_lines:expr, $want_err:expr) => {
+            #[test]
+            fn $name() {
+                let target = BufReader::new(&$target[..]);
+                let index = BufReader::new(&$index[..]);
+                let mut s = Select::new(target, index, $index_type, false);
+                let want_lines: Vec<&str> = $want_lines;
+                for want in want_lines {
+                    assert_eq!(Some(want.to_string()), s.next().map(|x| x.unwrap()));
+                }
+                match s.next() {
+                    Some(Err(SelectError::Io(x))) => {
+                        assert_eq!(io::ErrorKind::InvalidData, x.kind());
+                        assert!(
+                            x.to_string().starts_with($want_err),
+                            "want {} got {}",
+                            $want_err,
+                            x
+                        );
+                    }
+                    x => panic!("want io error got {:?}", x),
+                }
+                assert!(s.next().is_none());
+            }
+        };
+    }
+
+    test_select_io_error!(
+        select_io_error_target,
+        b"l1\n\xff\n",
+        b"a\na\n",
+        Some(Type::Re(Regex::new("^a").unwrap())),
+        vec!["l1\n"],
+        "Target|target=2|index=1|"
+    );
+    test_select_io_error!(
+        select_io_error_index_re,
+        b"l1\nl2\n",
+        b"a\n\xff\n",
+        Some(Type::Re(Regex::new("^a").unwrap())),
+        vec!["l1\n"],
+        "Re|target=2|index=2|"
+    );
+    test_select_io_error!(
+        select_io_error_index_number,
+        b"l1\nl2\nl3\n",
+        b"1\n\xff\n",
+        None,
+        vec!["l1\n"],
+        "Number|target=2|index=2|"
+    );
+
+    test_select_lines!(
+        select_lines_max_line_bytes_within,
+        "l1\nl2\n",
+        "a\na\n",
+        Some(Type::Re(Regex::new("a").unwrap())),
+        false,
+        vec!["l1\n", "l2\n"]
+    );
+
+    #[test]
+    fn select_lines_skip_blank_target() {
+        for (target, index, index_type, delimiter, want) in [
+            (
+                "l1\n\nl2\n\n\nl3\r\n\r\nl4\n",
+                "2\n3,\n",
+                None,
+                b'\n',
+                vec![(2, "l2\n"), (3, "l3\r\n"), (4, "l4\n")],
+            ),
+            (
+                "l1\n\nl2\nl3\n",
+                "a\nb\na\n",
+                Some(Type::Re(Regex::new("a").unwrap())),
+                b'\n',
+                vec![(1, "l1\n"), (3, "l3\n")],
+            ),
+            ("l1\0\0l2\0", "2\0", None, b'\0', vec![(2, "l2\0")]),
+        ] {
+            let got: Vec<(u64, String)> =
+                Select::new(target.as_bytes(), index.as_bytes(), index_type, false)
+                    .delimiter(delimiter)
+                    .skip_blank_target(true)
+                    .enumerate_target()
+                    .map(|x| x.unwrap())
+                    .collect();
+            let want: Vec<(u64, String)> =
+                want.into_iter().map(|(n, x)| (n, x.to_string())).collect();
+            assert_eq!(want, got);
+        }
+    }
+
+    #[test]
+    fn skip_rest_skip_blank_target() {
+        let mut s = Select::new("l1\n\nl2\n\n".as_bytes(), "1\n".as_bytes(), None, false)
+            .skip_blank_target(true);
+        assert_eq!(Some("l1\n".to_string()), s.next().map(|x| x.unwrap()));
+        s.skip_rest().unwrap();
+        assert_eq!(2, s.target_line());
+        assert_eq!(8, s.target_bytes());
+    }
+
+    #[test]
+    fn select_lines_cycle_index() {
+        for (target, index, invert, want, want_index_line) in [
+            (
+                "l1\nl2\nl3\nl4\nl5\n",
+                "a\nb\n",
+                false,
+                vec!["l1\n", "l3\n", "l5\n"],
+                1,
+            ),
+            (
+                "l1\nl2\nl3\nl4\nl5\n",
+                "a\nb\nb\n",
+                true,
+                vec!["l2\n", "l3\n", "l5\n"],
+                2,
+            ),
+            ("l1\nl2\n", "", false, vec![], 0),
+            ("l1\nl2\n", "", true, vec!["l1\n", "l2\n"], 0),
+        ] {
+            let mut s = Select::new(
+                target.as_bytes(),
+                index.as_bytes(),
+                Some(Type::Re(Regex::new("a").unwrap())),
+                invert,
+            )
+            .cycle_index(true);
+            let got: Vec<String> = s.by_ref().map(|x| x.unwrap()).collect();
+            assert_eq!(want, got, "index {:?}", index);
+            assert_eq!(want_index_line, s.index_line(), "index {:?}", index);
+        }
+    }
+
+    #[test]
+    fn select_lines_regex_timeout() {
+        let target = "l1\nl2\nl3\n";
+        let index = "b\na\nb\n";
+        let re = Type::Re(Regex::new("^a$").unwrap());
+        let got: Vec<String> = Select::new(target.as_bytes(), index.as_bytes(), Some(re), false)
+            .regex_timeout(Some(Duration::from_secs(60)))
+            .map(|x| x.unwrap())
+            .collect();
+        assert_eq!(vec!["l2\n"], got);
+    }
+
+    #[cfg(feature = "fancy")]
+    #[test]
+    fn select_lines_regex_timeout_exceeded() {
+        let target = "l1\nl2\nl3\n";
+        let index = format!("{}c\naa\n", "a".repeat(100));
+        let re: Regex = fancy_regex::RegexBuilder::new(r"^(?:(a)\1?)+$")
+            .backtrack_limit(usize::MAX)
+            .build()
+            .unwrap()
+            .into();
+        let mut s = Select::new(
+            target.as_bytes(),
+            index.as_bytes(),
+            Some(Type::Re(re)),
+            false,
+        )
+        .regex_timeout(Some(Duration::from_millis(100)));
+        match s.next() {
+            Some(Err(SelectError::Io(x))) => {
+                assert_eq!(io::ErrorKind::TimedOut, x.kind());
+                assert!(x.to_string().starts_with("Re|target=1|index=1|"));
+            }
+            x => panic!("want timeout got {:?}", x),
+        }
+        assert!(s.next().is_none());
+    }
+
+    #[test]
+    fn select_lines_max_line_bytes() {
+        for (target, index, delimiter, want_lines, want_err) in [
+            (
+                "l1\nllllllllll2\nl3\n",
+                "a\na\na\n",
+                b'\n',
+                vec!["l1\n"],
+                "Target|target=2|index=1|line exceeds 4 bytes",
+            ),
+            (
+                "l1\nl2\n",
+                "a\naaaaa\n",
+                b'\n',
+                vec!["l1\n"],
+                "Re|target=2|index=2|line exceeds 4 bytes",
+            ),
+            (
+                "l1\0l2\0lllll3",
+                "a\0a\0a\0",
+                b'\0',
+                vec!["l1\0", "l2\0"],
+                "Target|target=3|index=2|line exceeds 4 bytes",
+            ),
+        ] {
+            let mut s = Select::new(
+                BufReader::with_capacity(2, target.as_bytes()),
+                BufReader::new(index.as_bytes()),
+                Some(Type::Re(Regex::new("a").unwrap())),
+                false,
+            )
+            .delimiter(delimiter)
+            .max_line_bytes(Some(4));
+            for want in want_lines {
+                assert_eq!(Some(want.to_string()), s.next().map(|x| x.unwrap()));
+            }
+            match s.next() {
+                Some(Err(SelectError::Io(x))) => {
+                    assert_eq!(want_err, x.to_string());
+                }
+                x => panic!("want io error got {:?}", x),
+            }
+            assert!(s.next().is_none());
+        }
+    }
+
+    #[test]
+    fn select_io_error_skip_rest() {
+        let mut s = Select::new(
+            BufReader::new(&b"l1\nl2\n\xff\n"[..]),
+            BufReader::new("1\n".as_bytes()),
+            None,
+            false,
+        );
+        assert_eq!(Some("l1\n".to_string()), s.next().map(|x| x.unwrap()));
+        match s.skip_rest() {
+            Err(SelectError::Io(x)) => assert!(
+                x.to_string().starts_with("Target|target=3|index=1|"),
+                "got {}",
+                x
+            ),
+            x => panic!("want io error got {:?}", x),
+        }
+    }
+
     macro_rules! test_select {
         ($name:ident, $index:expr, $index_type:expr, $linum:expr, $want:expr, $want_inverse:expr) => {
             #[test]
@@ -1,3 +1,5 @@
+use std::io::{self, BufRead};
+
 /// Remove trailing newline from string.
 pub fn rstrip(s: &mut String) {
     if s.ends_with('\n') {
@@ -7,3 +9,81 @@ pub fn rstrip(s: &mut String) {
         }
     }
 }
+
+/// Return string without trailing newline.
+pub fn strip_newline(s: &str) -> &str {
+    s.strip_suffix('\n')
+        .map(|x| x.strip_suffix('\r').unwrap_or(x))
+        .unwrap_or(s)
+}
+
+/// Return string without trailing delimiter, trailing newline is removed as [strip_newline] does.
+pub fn strip_delimiter(s: &str, delimiter: u8) -> &str {
+    match delimiter {
+        b'\n' => strip_newline(s),
+        d => s.strip_suffix(d as char).unwrap_or(s),
+    }
+}
+
+/// Remove trailing delimiter from string, trailing newline is removed as [rstrip] does.
+pub fn rstrip_delimiter(s: &mut String, delimiter: u8) {
+    if delimiter == b'\n' {
+        rstrip(s);
+    } else if s.as_bytes().last() == Some(&delimiter) {
+        s.pop();
+    }
+}
+
+/// Read a record terminated by the ASCII delimiter into the string,
+/// same as [BufRead::read_line] when the delimiter is newline.
+pub fn read_record<R: BufRead>(r: &mut R, delimiter: u8, buf: &mut String) -> io::Result<usize> {
+    if delimiter == b'\n' {
+        return r.read_line(buf);
+    }
+    let mut bytes = Vec::new();
+    let n = r.read_until(delimiter, &mut bytes)?;
+    let s = String::from_utf8(bytes).map_err(|x| io::Error::new(io::ErrorKind::InvalidData, x))?;
+    buf.push_str(&s);
+    Ok(n)
+}
+
+/// Read a record like [read_record],
+/// but fail with [io::ErrorKind::InvalidData] instead of buffering a record
+/// whose content without the delimiter exceeds `limit` bytes.
+pub fn read_record_limited<R: BufRead>(
+    r: &mut R,
+    delimiter: u8,
+    buf: &mut String,
+    limit: usize,
+) -> io::Result<usize> {
+    let mut bytes = Vec::new();
+    loop {
+        let available = match r.fill_buf() {
+            Ok(x) => x,
+            Err(x) if x.kind() == io::ErrorKind::Interrupted => continue,
+            Err(x) => return Err(x),
+        };
+        if available.is_empty() {
+            break;
+        }
+        let (found, used) = match available.iter().position(|&b| b == delimiter) {
+            Some(i) => (true, i + 1),
+            None => (false, available.len()),
+        };
+        if bytes.len() + used - usize::from(found) > limit {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidData,
+                format!("line exceeds {} bytes", limit),
+            ));
+        }
+        bytes.extend_from_slice(&available[..used]);
+        r.consume(used);
+        if found {
+            break;
+        }
+    }
+    let n = bytes.len();
+    let s = String::from_utf8(bytes).map_err(|x| io::Error::new(io::ErrorKind::InvalidData, x))?;
+    buf.push_str(&s);
+    Ok(n)
+}